@@ -0,0 +1,195 @@
+use super::math::Vec4;
+use super::shader::Varyings;
+
+/// Signed distances of a clip-space position to the six frustum planes.
+/// A point is inside the view volume when all of them are non-negative.
+fn plane_distances(v: Vec4) -> [f32; 6] {
+    [
+        v.w + v.x, // left
+        v.w - v.x, // right
+        v.w + v.y, // bottom
+        v.w - v.y, // top
+        v.z,       // near
+        v.w - v.z, // far
+    ]
+}
+
+pub fn is_inside(v: Vec4) -> bool {
+    plane_distances(v).iter().all(|&d| d >= 0.0)
+}
+
+/// Liang–Barsky clipping of the segment `a`–`b` against the view volume.
+///
+/// Returns the parameter range `(t0, t1)` of the visible part, where 0 is `a`
+/// and 1 is `b`, or `None` when the whole segment is outside.
+pub fn clip_line(a: Vec4, b: Vec4) -> Option<(f32, f32)> {
+    let da = plane_distances(a);
+    let db = plane_distances(b);
+
+    let mut t0: f32 = 0.0;
+    let mut t1: f32 = 1.0;
+
+    for (&pa, &pb) in da.iter().zip(db.iter()) {
+        if pa < 0.0 && pb < 0.0 {
+            return None;
+        }
+        if pa < 0.0 {
+            // Entering the half-space
+            t0 = t0.max(pa / (pa - pb));
+        } else if pb < 0.0 {
+            // Leaving the half-space
+            t1 = t1.min(pa / (pa - pb));
+        }
+    }
+
+    if t0 > t1 { None } else { Some((t0, t1)) }
+}
+
+/// A polygon corner carried through clipping.
+#[derive(Debug, Clone, Copy)]
+pub struct ClipVertex {
+    pub position: Vec4,
+    pub varyings: Varyings,
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        ClipVertex {
+            position: self.position.lerp(other.position, t),
+            varyings: self.varyings.lerp(&other.varyings, t),
+        }
+    }
+}
+
+/// Sutherland–Hodgman clipping of a convex polygon against the view volume.
+///
+/// Varyings are interpolated linearly in clip space, which keeps them correct
+/// after the perspective divide. The result is empty if nothing is visible.
+pub fn clip_polygon(polygon: &[ClipVertex]) -> Vec<ClipVertex> {
+    let mut output = polygon.to_vec();
+
+    for plane in 0..6 {
+        if output.is_empty() {
+            break;
+        }
+
+        let input = std::mem::take(&mut output);
+        for (i, current) in input.iter().enumerate() {
+            let previous = &input[(i + input.len() - 1) % input.len()];
+            let dc = plane_distances(current.position)[plane];
+            let dp = plane_distances(previous.position)[plane];
+
+            if dc >= 0.0 {
+                if dp < 0.0 {
+                    output.push(previous.lerp(current, dp / (dp - dc)));
+                }
+                output.push(*current);
+            } else if dp >= 0.0 {
+                output.push(previous.lerp(current, dp / (dp - dc)));
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32, z: f32, w: f32, value: f32) -> ClipVertex {
+        let mut varyings = Varyings::default();
+        varyings.values[0] = value;
+        ClipVertex {
+            position: Vec4::new(x, y, z, w),
+            varyings,
+        }
+    }
+
+    #[test]
+    fn is_inside_includes_the_boundary() {
+        assert!(is_inside(Vec4::new(0.0, 0.0, 0.5, 1.0)));
+        assert!(is_inside(Vec4::new(1.0, -1.0, 0.0, 1.0)));
+        assert!(is_inside(Vec4::new(-2.0, 2.0, 2.0, 2.0)));
+        assert!(!is_inside(Vec4::new(1.5, 0.0, 0.5, 1.0)));
+        assert!(!is_inside(Vec4::new(0.0, 0.0, -0.1, 1.0)));
+        assert!(!is_inside(Vec4::new(0.0, 0.0, 1.1, 1.0)));
+    }
+
+    #[test]
+    fn clip_line_keeps_inside_segments_whole() {
+        let a = Vec4::new(-0.5, 0.0, 0.5, 1.0);
+        let b = Vec4::new(0.5, 0.5, 0.5, 1.0);
+        assert_eq!(clip_line(a, b), Some((0.0, 1.0)));
+    }
+
+    #[test]
+    fn clip_line_cuts_at_the_planes() {
+        // From x = -3 to x = 1, entering through the left plane at x = -1
+        let a = Vec4::new(-3.0, 0.0, 0.5, 1.0);
+        let b = Vec4::new(1.0, 0.0, 0.5, 1.0);
+        assert_eq!(clip_line(a, b), Some((0.5, 1.0)));
+
+        // Crossing the near plane, behind the camera at the start
+        let a = Vec4::new(0.0, 0.0, -1.0, 1.0);
+        let b = Vec4::new(0.0, 0.0, 3.0, 4.0);
+        assert_eq!(clip_line(a, b), Some((0.25, 1.0)));
+    }
+
+    #[test]
+    fn clip_line_rejects_outside_segments() {
+        // Both ends beyond the right plane
+        let a = Vec4::new(2.0, 0.0, 0.5, 1.0);
+        let b = Vec4::new(3.0, 1.0, 0.5, 1.0);
+        assert_eq!(clip_line(a, b), None);
+
+        // Passing outside the corner between the left and top planes
+        let a = Vec4::new(-3.0, 0.0, 0.5, 1.0);
+        let b = Vec4::new(0.0, 3.0, 0.5, 1.0);
+        assert_eq!(clip_line(a, b), None);
+    }
+
+    #[test]
+    fn clip_polygon_keeps_inside_triangles() {
+        let triangle = [
+            vertex(0.0, 0.0, 0.5, 1.0, 0.0),
+            vertex(0.5, 0.0, 0.5, 1.0, 1.0),
+            vertex(0.0, 0.5, 0.5, 1.0, 2.0),
+        ];
+        let clipped = clip_polygon(&triangle);
+        assert_eq!(clipped.len(), 3);
+        for (a, b) in clipped.iter().zip(&triangle) {
+            assert_eq!(a.position, b.position);
+        }
+    }
+
+    #[test]
+    fn clip_polygon_cuts_corners_and_interpolates_varyings() {
+        // The corner at x = 2 sticks out of the right plane, leaving a quad
+        let triangle = [
+            vertex(0.0, -0.5, 0.5, 1.0, 0.0),
+            vertex(2.0, 0.0, 0.5, 1.0, 2.0),
+            vertex(0.0, 0.5, 0.5, 1.0, 0.0),
+        ];
+        let clipped = clip_polygon(&triangle);
+        assert_eq!(clipped.len(), 4);
+        for v in &clipped {
+            assert!(is_inside(v.position), "{:?}", v.position);
+            // The varying equals x along both cut edges
+            assert!((v.varyings.values[0] - v.position.x).abs() < 1e-5);
+        }
+        let on_plane = clipped.iter().filter(|v| v.position.x == 1.0).count();
+        assert_eq!(on_plane, 2);
+    }
+
+    #[test]
+    fn clip_polygon_removes_invisible_triangles() {
+        // Entirely behind the near plane
+        let triangle = [
+            vertex(0.0, 0.0, -1.0, 1.0, 0.0),
+            vertex(0.5, 0.0, -1.0, 1.0, 0.0),
+            vertex(0.0, 0.5, -2.0, 1.0, 0.0),
+        ];
+        assert!(clip_polygon(&triangle).is_empty());
+    }
+}
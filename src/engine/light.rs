@@ -0,0 +1,315 @@
+use super::object::Point;
+use super::shader::Color;
+
+#[derive(Debug, Clone, Copy)]
+pub enum LightKind {
+    /// Uniform light that reaches every surface regardless of its orientation.
+    Ambient,
+    /// Parallel rays travelling along `direction`, like sunlight.
+    Directional { direction: Point },
+    /// Light radiating in all directions from `position`.
+    Point { position: Point },
+    /// A cone of light from `position` along `direction`. Angles are in radians
+    /// from the cone axis; intensity fades out between `inner_angle` and `outer_angle`.
+    Spot {
+        position: Point,
+        direction: Point,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+/// Distance falloff `1 / (constant + linear * d + quadratic * d^2)`.
+#[derive(Debug, Clone, Copy)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    pub const NONE: Attenuation = Attenuation {
+        constant: 1.0,
+        linear: 0.0,
+        quadratic: 0.0,
+    };
+
+    pub fn factor(&self, distance: f32) -> f32 {
+        let denom = self.constant + self.linear * distance + self.quadratic * distance * distance;
+        if denom <= 0.0 { 1.0 } else { 1.0 / denom }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Color,
+    pub intensity: f32,
+    /// Only used by point and spot lights.
+    pub attenuation: Attenuation,
+}
+
+impl Light {
+    pub fn ambient(color: Color, intensity: f32) -> Self {
+        Self::new(LightKind::Ambient, color, intensity)
+    }
+
+    pub fn directional(direction: Point, color: Color, intensity: f32) -> Self {
+        let direction = direction.normalize();
+        Self::new(LightKind::Directional { direction }, color, intensity)
+    }
+
+    pub fn point(position: Point, color: Color, intensity: f32) -> Self {
+        Self::new(LightKind::Point { position }, color, intensity)
+    }
+
+    pub fn spot(
+        position: Point,
+        direction: Point,
+        inner_angle: f32,
+        outer_angle: f32,
+        color: Color,
+        intensity: f32,
+    ) -> Self {
+        let direction = direction.normalize();
+        let kind = LightKind::Spot {
+            position,
+            direction,
+            inner_angle,
+            outer_angle,
+        };
+        Self::new(kind, color, intensity)
+    }
+
+    fn new(kind: LightKind, color: Color, intensity: f32) -> Self {
+        Light {
+            kind,
+            color,
+            intensity,
+            attenuation: Attenuation::NONE,
+        }
+    }
+
+    pub fn with_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Self {
+        self.attenuation = Attenuation {
+            constant,
+            linear,
+            quadratic,
+        };
+        self
+    }
+
+    /// Unit vector from `position` towards the light and the strength reaching it,
+    /// after attenuation and spot cone falloff. Ambient lights have no direction.
+    pub fn incidence(&self, position: Point) -> (Option<Point>, f32) {
+        match self.kind {
+            LightKind::Ambient => (None, self.intensity),
+            LightKind::Directional { direction } => (Some(-direction), self.intensity),
+            LightKind::Point { position: source } => {
+                let offset = source - position;
+                let distance = offset.length();
+                let strength = self.intensity * self.attenuation.factor(distance);
+                (Some(offset.normalize()), strength)
+            }
+            LightKind::Spot {
+                position: source,
+                direction,
+                inner_angle,
+                outer_angle,
+            } => {
+                let offset = source - position;
+                let distance = offset.length();
+                let to_light = offset.normalize();
+
+                // Angle between the cone axis and the ray reaching the surface
+                let cos_angle = -to_light.dot(direction);
+                let cos_inner = inner_angle.cos();
+                let cos_outer = outer_angle.cos();
+                let cone = if cos_angle >= cos_inner {
+                    1.0
+                } else if cos_angle <= cos_outer {
+                    0.0
+                } else {
+                    (cos_angle - cos_outer) / (cos_inner - cos_outer)
+                };
+
+                let strength = self.intensity * self.attenuation.factor(distance) * cone;
+                (Some(to_light), strength)
+            }
+        }
+    }
+
+    /// Light reaching a surface, split into ambient, diffuse (Lambertian) and
+    /// Blinn-Phong specular terms. `eye` is the viewer position.
+    pub fn contribution(
+        &self,
+        position: Point,
+        normal: Point,
+        eye: Point,
+        shininess: f32,
+    ) -> Lighting {
+        let mut lighting = Lighting::default();
+        let (to_light, strength) = self.incidence(position);
+
+        let Some(l) = to_light else {
+            lighting.ambient = self.color.scaled_rgb(strength);
+            return lighting;
+        };
+
+        let lambert = normal.dot(l);
+        if lambert <= 0.0 {
+            return lighting;
+        }
+        lighting.diffuse = self.color.scaled_rgb(strength * lambert);
+
+        let v = (eye - position).normalize();
+        let half = (l + v).normalize();
+        let highlight = normal.dot(half).max(0.0).powf(shininess);
+        lighting.specular = self.color.scaled_rgb(strength * highlight);
+
+        lighting
+    }
+}
+
+/// Light arriving at a surface point, as linear RGB factors per term.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lighting {
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+}
+
+impl Lighting {
+    fn accumulate(&mut self, other: &Lighting) {
+        for i in 0..3 {
+            self.ambient[i] += other.ambient[i];
+            self.diffuse[i] += other.diffuse[i];
+            self.specular[i] += other.specular[i];
+        }
+    }
+}
+
+/// Sum of the contributions of all `lights` at a surface point seen from `eye`.
+pub fn illuminate(
+    lights: &[Light],
+    position: Point,
+    normal: Point,
+    eye: Point,
+    shininess: f32,
+) -> Lighting {
+    let mut total = Lighting::default();
+    for light in lights {
+        total.accumulate(&light.contribution(position, normal, eye, shininess));
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rgb(actual: [f32; 3], expected: f32) {
+        for v in actual {
+            assert!((v - expected).abs() < 1e-4, "{actual:?} != {expected}");
+        }
+    }
+
+    // Lighting of a surface at the origin facing -Z, seen from straight in front
+    fn light_facing(light: &Light) -> Lighting {
+        let normal = Point::new(0.0, 0.0, -1.0);
+        light.contribution(Point::default(), normal, Point::new(0.0, 0.0, -10.0), 32.0)
+    }
+
+    #[test]
+    fn directional_light_follows_lambert() {
+        let head_on = Light::directional(Point::new(0.0, 0.0, 1.0), Color::WHITE, 1.0);
+        assert_rgb(light_facing(&head_on).diffuse, 1.0);
+
+        // 60 degrees from the normal
+        let angle = 60f32.to_radians();
+        let slanted =
+            Light::directional(Point::new(angle.sin(), 0.0, angle.cos()), Color::WHITE, 1.0);
+        let lighting = light_facing(&slanted);
+        assert_rgb(lighting.diffuse, 0.5);
+        assert_rgb(lighting.ambient, 0.0);
+
+        let behind = Light::directional(Point::new(0.0, 0.0, -1.0), Color::WHITE, 1.0);
+        let lighting = light_facing(&behind);
+        assert_rgb(lighting.diffuse, 0.0);
+        assert_rgb(lighting.specular, 0.0);
+    }
+
+    #[test]
+    fn ambient_light_ignores_orientation() {
+        let ambient = Light::ambient(Color::WHITE, 0.25);
+        let lighting = light_facing(&ambient);
+        assert_rgb(lighting.ambient, 0.25);
+        assert_rgb(lighting.diffuse, 0.0);
+        assert_eq!(ambient.incidence(Point::default()).0, None);
+    }
+
+    #[test]
+    fn point_lights_fade_with_distance() {
+        let light =
+            Light::point(Point::default(), Color::WHITE, 2.0).with_attenuation(1.0, 0.1, 0.01);
+        for (distance, expected) in [(0.0, 2.0), (10.0, 2.0 / 3.0), (20.0, 2.0 / 7.0)] {
+            let (direction, strength) = light.incidence(Point::new(0.0, -distance, 0.0));
+            assert!((strength - expected).abs() < 1e-5, "{distance}: {strength}");
+            if distance > 0.0 {
+                assert_eq!(direction, Some(Point::new(0.0, 1.0, 0.0)));
+            }
+        }
+
+        // Without attenuation the distance does not matter
+        let constant = Light::point(Point::default(), Color::WHITE, 1.0);
+        assert_eq!(constant.incidence(Point::new(500.0, 0.0, 0.0)).1, 1.0);
+    }
+
+    #[test]
+    fn spot_lights_only_reach_inside_their_cone() {
+        let (inner, outer) = (20f32.to_radians(), 30f32.to_radians());
+        let light = Light::spot(
+            Point::new(0.0, 0.0, -10.0),
+            Point::new(0.0, 0.0, 1.0),
+            inner,
+            outer,
+            Color::WHITE,
+            1.0,
+        );
+        // Points on the plane z = 0 at an angle from the axis
+        let at = |degrees: f32| {
+            let position = Point::new(10.0 * degrees.to_radians().tan(), 0.0, 0.0);
+            light.incidence(position).1
+        };
+        assert_eq!(at(0.0), 1.0);
+        assert_eq!(at(15.0), 1.0);
+        assert_eq!(at(35.0), 0.0);
+        assert_eq!(at(80.0), 0.0);
+        let between = at(25.0);
+        assert!(between > 0.0 && between < 1.0, "{between}");
+        assert!(at(22.0) > at(28.0));
+    }
+
+    #[test]
+    fn specular_peaks_where_the_normal_is_the_half_vector() {
+        // Light and eye 60 degrees to either side of the normal, so the half
+        // vector between them is the normal itself
+        let angle = 60f32.to_radians();
+        let light = Light::directional(
+            Point::new(-angle.sin(), 0.0, angle.cos()),
+            Color::WHITE,
+            1.0,
+        );
+        let eye = Point::new(-angle.sin(), 0.0, -angle.cos()) * 10.0;
+        let shine = |normal: Point| light.contribution(Point::default(), normal, eye, 32.0);
+
+        let peak = shine(Point::new(0.0, 0.0, -1.0));
+        assert_rgb(peak.specular, 1.0);
+        assert_rgb(peak.diffuse, 0.5);
+
+        // Tilting the normal away drops the highlight as cos^shininess
+        let tilt = 10f32.to_radians();
+        let tilted = shine(Point::new(tilt.sin(), 0.0, -tilt.cos()));
+        assert_rgb(tilted.specular, tilt.cos().powf(32.0));
+    }
+}
@@ -0,0 +1,596 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use super::object::{Matrix4x4, Point};
+
+/// Implements component-wise `+`, `-`, unary `-`, and scaling by `f32` for a vector type.
+macro_rules! vector_ops {
+    ($name:ident { $($field:ident),+ }) => {
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, rhs: $name) -> $name {
+                $name { $($field: self.$field + rhs.$field),+ }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, rhs: $name) -> $name {
+                $name { $($field: self.$field - rhs.$field),+ }
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = $name;
+
+            fn mul(self, rhs: f32) -> $name {
+                $name { $($field: self.$field * rhs),+ }
+            }
+        }
+
+        impl Mul<$name> for f32 {
+            type Output = $name;
+
+            fn mul(self, rhs: $name) -> $name {
+                rhs * self
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = $name;
+
+            fn div(self, rhs: f32) -> $name {
+                $name { $($field: self.$field / rhs),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name { $($field: -self.$field),+ }
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: $name) {
+                $(self.$field += rhs.$field;)+
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: $name) {
+                $(self.$field -= rhs.$field;)+
+            }
+        }
+
+        impl $name {
+            pub fn new($($field: f32),+) -> Self {
+                $name { $($field),+ }
+            }
+
+            pub fn dot(self, other: $name) -> f32 {
+                0.0 $(+ self.$field * other.$field)+
+            }
+
+            pub fn length(self) -> f32 {
+                self.dot(self).sqrt()
+            }
+
+            /// Returns the vector scaled to unit length, or unchanged if it has no length.
+            pub fn normalize(self) -> $name {
+                let len = self.length();
+                if len == 0.0 {
+                    return self;
+                }
+                self / len
+            }
+
+            /// Linear interpolation towards `other`, with `t` in `0.0..=1.0`.
+            pub fn lerp(self, other: $name, t: f32) -> $name {
+                self + (other - self) * t
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+vector_ops!(Vec2 { x, y });
+
+/// A 3D vector. `object::Point` is an alias of this type.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+vector_ops!(Vec3 { x, y, z });
+
+impl Vec3 {
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3 {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    /// Homogeneous vector with the given `w`; 1 for positions, 0 for directions.
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            w,
+        }
+    }
+}
+
+/// A homogeneous 4D vector.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+vector_ops!(Vec4 { x, y, z, w });
+
+impl Vec4 {
+    /// Drops `w` without dividing by it.
+    pub fn truncate(self) -> Vec3 {
+        Vec3 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        }
+    }
+
+    /// Divides by `w`, turning a homogeneous position back into a 3D point.
+    pub fn project(self) -> Vec3 {
+        self.truncate() / self.w
+    }
+}
+
+impl From<Vec3> for Vec4 {
+    fn from(v: Vec3) -> Vec4 {
+        v.extend(1.0)
+    }
+}
+
+impl Mul<Vec4> for Matrix4x4 {
+    type Output = Vec4;
+
+    fn mul(self, v: Vec4) -> Vec4 {
+        let d = &self.data;
+        let row = |r: [f32; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z + r[3] * v.w;
+        Vec4 {
+            x: row(d[0]),
+            y: row(d[1]),
+            z: row(d[2]),
+            w: row(d[3]),
+        }
+    }
+}
+
+/// Transforms a position, treating it as `w = 1` and ignoring the projective row.
+impl Mul<Point> for Matrix4x4 {
+    type Output = Point;
+
+    fn mul(self, p: Point) -> Point {
+        (self * p.extend(1.0)).truncate()
+    }
+}
+
+impl Mul for Matrix4x4 {
+    type Output = Matrix4x4;
+
+    fn mul(self, rhs: Matrix4x4) -> Matrix4x4 {
+        self.multiply(&rhs)
+    }
+}
+
+/// A rotation stored as a unit quaternion `w + xi + yj + zk`.
+///
+/// Unlike a rotation matrix that is multiplied every frame, a quaternion can be
+/// renormalised cheaply, so accumulated rotations never shear or scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    pub fn identity() -> Self {
+        Quaternion {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    /// Rotation of `angle` radians around `axis`, which does not need to be unit length.
+    pub fn from_axis_angle(axis: Point, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Quaternion {
+            w: cos,
+            x: axis.x * sin,
+            y: axis.y * sin,
+            z: axis.z * sin,
+        }
+    }
+
+    /// Rotation axis and angle in radians. The axis is +X for the identity.
+    pub fn to_axis_angle(&self) -> (Point, f32) {
+        let q = self.normalize();
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        let sin = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if sin < 1e-6 {
+            return (Point::new(1.0, 0.0, 0.0), 0.0);
+        }
+        (Point::new(q.x, q.y, q.z) / sin, angle)
+    }
+
+    /// Rotation by `x`, then `y`, then `z` radians around the fixed world axes,
+    /// matching `Matrix4x4::from_euler`.
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        let (sx, cx) = (x / 2.0).sin_cos();
+        let (sy, cy) = (y / 2.0).sin_cos();
+        let (sz, cz) = (z / 2.0).sin_cos();
+        Quaternion {
+            w: cx * cy * cz + sx * sy * sz,
+            x: sx * cy * cz - cx * sy * sz,
+            y: cx * sy * cz + sx * cy * sz,
+            z: cx * cy * sz - sx * sy * cz,
+        }
+    }
+
+    /// Euler angles `(x, y, z)` in radians, the inverse of `from_euler`.
+    pub fn to_euler(&self) -> (f32, f32, f32) {
+        let q = self.normalize();
+        let x = (2.0 * (q.w * q.x + q.y * q.z)).atan2(1.0 - 2.0 * (q.x * q.x + q.y * q.y));
+        let y = (2.0 * (q.w * q.y - q.z * q.x)).clamp(-1.0, 1.0).asin();
+        let z = (2.0 * (q.w * q.z + q.x * q.y)).atan2(1.0 - 2.0 * (q.y * q.y + q.z * q.z));
+        (x, y, z)
+    }
+
+    /// Extracts the rotation from the upper 3x3 part of a pure rotation matrix.
+    pub fn from_matrix(m: &Matrix4x4) -> Self {
+        let d = &m.data;
+        let trace = d[0][0] + d[1][1] + d[2][2];
+
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion {
+                w: 0.25 * s,
+                x: (d[2][1] - d[1][2]) / s,
+                y: (d[0][2] - d[2][0]) / s,
+                z: (d[1][0] - d[0][1]) / s,
+            }
+        } else if d[0][0] > d[1][1] && d[0][0] > d[2][2] {
+            let s = (1.0 + d[0][0] - d[1][1] - d[2][2]).sqrt() * 2.0;
+            Quaternion {
+                w: (d[2][1] - d[1][2]) / s,
+                x: 0.25 * s,
+                y: (d[0][1] + d[1][0]) / s,
+                z: (d[0][2] + d[2][0]) / s,
+            }
+        } else if d[1][1] > d[2][2] {
+            let s = (1.0 + d[1][1] - d[0][0] - d[2][2]).sqrt() * 2.0;
+            Quaternion {
+                w: (d[0][2] - d[2][0]) / s,
+                x: (d[0][1] + d[1][0]) / s,
+                y: 0.25 * s,
+                z: (d[1][2] + d[2][1]) / s,
+            }
+        } else {
+            let s = (1.0 + d[2][2] - d[0][0] - d[1][1]).sqrt() * 2.0;
+            Quaternion {
+                w: (d[1][0] - d[0][1]) / s,
+                x: (d[0][2] + d[2][0]) / s,
+                y: (d[1][2] + d[2][1]) / s,
+                z: 0.25 * s,
+            }
+        };
+
+        q.normalize()
+    }
+
+    pub fn to_matrix(&self) -> Matrix4x4 {
+        let Quaternion { w, x, y, z } = self.normalize();
+        Matrix4x4 {
+            data: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - w * z),
+                    2.0 * (x * z + w * y),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * y + w * z),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - w * x),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * z - w * y),
+                    2.0 * (y * z + w * x),
+                    1.0 - 2.0 * (x * x + y * y),
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Hamilton product; the result applies `other` first, then `self`.
+    pub fn multiply(&self, other: &Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Returns the quaternion scaled to unit length, or the identity if it has no length.
+    pub fn normalize(&self) -> Quaternion {
+        let len = self.length();
+        if len == 0.0 {
+            return Quaternion::identity();
+        }
+        Quaternion {
+            w: self.w / len,
+            x: self.x / len,
+            y: self.y / len,
+            z: self.z / len,
+        }
+    }
+
+    pub fn rotate_point(&self, p: &Point) -> Point {
+        let v = Quaternion {
+            w: 0.0,
+            x: p.x,
+            y: p.y,
+            z: p.z,
+        };
+        let q = self.normalize();
+        let r = q.multiply(&v).multiply(&q.conjugate());
+        Point::new(r.x, r.y, r.z)
+    }
+
+    /// Spherical linear interpolation along the shortest arc, with `t` in `0.0..=1.0`.
+    pub fn slerp(&self, other: &Quaternion, t: f32) -> Quaternion {
+        let a = self.normalize();
+        let mut b = other.normalize();
+
+        // q and -q are the same rotation; go the short way round
+        let mut cos = a.dot(&b);
+        if cos < 0.0 {
+            b = Quaternion {
+                w: -b.w,
+                x: -b.x,
+                y: -b.y,
+                z: -b.z,
+            };
+            cos = -cos;
+        }
+
+        // Nearly parallel: fall back to normalised linear interpolation
+        let (wa, wb) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        Quaternion {
+            w: a.w * wa + b.w * wb,
+            x: a.x * wa + b.x * wb,
+            y: a.y * wa + b.y * wb,
+            z: a.z * wa + b.z * wb,
+        }
+        .normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    // q and -q are the same rotation
+    fn assert_same_rotation(a: Quaternion, b: Quaternion) {
+        assert!(a.dot(&b).abs() > 1.0 - 1e-5, "{a:?} != {b:?}");
+    }
+
+    fn rotations() -> Vec<Quaternion> {
+        let mut rotations = vec![
+            Quaternion::identity(),
+            Quaternion::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), PI),
+            Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), PI),
+            Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), PI),
+            Quaternion::from_axis_angle(Vec3::new(1.0, 1.0, 0.0), 3.0),
+        ];
+        for i in 0..20 {
+            let i = i as f32;
+            let axis = Vec3::new((i * 1.3).sin(), (i * 0.7).cos(), (i * 2.1).sin() + 0.1);
+            rotations.push(Quaternion::from_axis_angle(axis, i * 0.37 - 3.0));
+        }
+        rotations
+    }
+
+    #[test]
+    fn vector_operators_work_per_component() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(4.0, -5.0, 6.0);
+        assert_eq!(a + b, Vec3::new(5.0, -3.0, 9.0));
+        assert_eq!(a - b, Vec3::new(-3.0, 7.0, -3.0));
+        assert_eq!(a * 2.0, 2.0 * a);
+        assert_eq!(a / 2.0, Vec3::new(0.5, 1.0, 1.5));
+        assert_eq!(-a, Vec3::new(-1.0, -2.0, -3.0));
+        assert_eq!(a.dot(b), 12.0);
+        assert_eq!(a.lerp(b, 0.5), Vec3::new(2.5, -1.5, 4.5));
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        assert_eq!(c, b);
+
+        assert_eq!(Vec2::new(3.0, 4.0).length(), 5.0);
+        assert_eq!(Vec2::default().normalize(), Vec2::default());
+    }
+
+    #[test]
+    fn cross_product_is_right_handed() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        assert_eq!(x.cross(y), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(y.cross(x), Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn homogeneous_positions_are_translated_and_projected() {
+        let p = Vec3::new(1.0, 2.0, 3.0);
+        let moved = Matrix4x4::translate(1.0, 1.0, 1.0) * Vec4::from(p);
+        assert_eq!(moved, Vec4::new(2.0, 3.0, 4.0, 1.0));
+        // Directions are not translated
+        let direction = Matrix4x4::translate(1.0, 1.0, 1.0) * p.extend(0.0);
+        assert_eq!(direction.truncate(), p);
+        assert_eq!(Vec4::new(2.0, 4.0, 6.0, 2.0).project(), p);
+    }
+
+    #[test]
+    fn axis_angle_rotates_counter_clockwise_around_axis() {
+        let q = Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 2.0), FRAC_PI_2);
+        assert_close(
+            q.rotate_point(&Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+
+        let (axis, angle) = q.to_axis_angle();
+        assert_close(axis, Vec3::new(0.0, 0.0, 1.0));
+        assert!((angle - FRAC_PI_2).abs() < 1e-5);
+        assert_eq!(Quaternion::identity().to_axis_angle().1, 0.0);
+    }
+
+    #[test]
+    fn rotate_point_matches_to_matrix() {
+        let p = Vec3::new(1.0, -2.0, 3.0);
+        for q in rotations() {
+            assert_close(q.rotate_point(&p), q.to_matrix() * p);
+        }
+    }
+
+    #[test]
+    fn from_matrix_inverts_to_matrix() {
+        for q in rotations() {
+            assert_same_rotation(Quaternion::from_matrix(&q.to_matrix()), q);
+        }
+    }
+
+    #[test]
+    fn euler_angles_match_matrix_and_round_trip() {
+        let p = Vec3::new(0.5, 1.0, -2.0);
+        for (x, y, z) in [(0.3, -0.7, 1.1), (-2.5, 1.2, 0.4), (1.0, 0.0, -3.0)] {
+            let q = Quaternion::from_euler(x, y, z);
+            assert_close(q.rotate_point(&p), Matrix4x4::from_euler(x, y, z) * p);
+
+            let (rx, ry, rz) = q.to_euler();
+            assert_close(Vec3::new(rx, ry, rz), Vec3::new(x, y, z));
+        }
+    }
+
+    #[test]
+    fn multiply_applies_right_operand_first() {
+        let a = Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), FRAC_PI_2);
+        let b = Quaternion::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), FRAC_PI_2);
+        let p = Vec3::new(0.0, 1.0, 0.0);
+        // b takes +Y to +Z, which a leaves alone
+        assert_close(a.multiply(&b).rotate_point(&p), Vec3::new(0.0, 0.0, 1.0));
+        assert_close(
+            a.multiply(&b).rotate_point(&p),
+            a.rotate_point(&b.rotate_point(&p)),
+        );
+        assert_same_rotation(a.multiply(&a.conjugate()), Quaternion::identity());
+    }
+
+    #[test]
+    fn normalize_keeps_rotations_unit_length() {
+        let q = Quaternion {
+            w: 2.0,
+            x: 0.0,
+            y: 2.0,
+            z: 0.0,
+        };
+        assert!((q.normalize().length() - 1.0).abs() < 1e-6);
+        let zero = Quaternion {
+            w: 0.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        assert_eq!(zero.normalize(), Quaternion::identity());
+    }
+
+    #[test]
+    fn slerp_follows_the_shortest_arc() {
+        let axis = Vec3::new(0.0, 1.0, 0.0);
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(axis, FRAC_PI_2);
+
+        assert_same_rotation(a.slerp(&b, 0.0), a);
+        assert_same_rotation(a.slerp(&b, 1.0), b);
+        assert_same_rotation(
+            a.slerp(&b, 0.5),
+            Quaternion::from_axis_angle(axis, FRAC_PI_4),
+        );
+
+        // The same rotation as `b`, the long way round
+        let negated = Quaternion {
+            w: -b.w,
+            x: -b.x,
+            y: -b.y,
+            z: -b.z,
+        };
+        assert_same_rotation(
+            a.slerp(&negated, 0.5),
+            Quaternion::from_axis_angle(axis, FRAC_PI_4),
+        );
+    }
+}
@@ -1,3 +1,11 @@
+pub mod clip;
+pub mod light;
+pub mod math;
 pub mod object;
+pub mod pixel;
+pub mod recorder;
+pub mod render;
 pub mod shader;
 pub mod space;
+pub mod svg;
+pub mod texture;
@@ -1,3 +1,9 @@
+use std::rc::Rc;
+
+use super::math::{Quaternion, Vec2};
+use super::shader::Color;
+use super::texture::Texture;
+
 #[derive(Clone, Copy)]
 pub struct Matrix4x4 {
     pub data: [[f32; 4]; 4],
@@ -58,6 +64,84 @@ impl Matrix4x4 {
         }
     }
 
+    pub fn translate(x: f32, y: f32, z: f32) -> Self {
+        Matrix4x4 {
+            data: [
+                [1.0, 0.0, 0.0, x],
+                [0.0, 1.0, 0.0, y],
+                [0.0, 0.0, 1.0, z],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn scale(x: f32, y: f32, z: f32) -> Self {
+        Matrix4x4 {
+            data: [
+                [x, 0.0, 0.0, 0.0],
+                [0.0, y, 0.0, 0.0],
+                [0.0, 0.0, z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Rotation by `x`, then `y`, then `z` radians around the fixed world axes.
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        Matrix4x4::rotate_z(z)
+            .multiply(&Matrix4x4::rotate_y(y))
+            .multiply(&Matrix4x4::rotate_x(x))
+    }
+
+    pub fn transpose(&self) -> Matrix4x4 {
+        let mut result = Matrix4x4::identity();
+        for i in 0..4 {
+            for j in 0..4 {
+                result.data[i][j] = self.data[j][i];
+            }
+        }
+        result
+    }
+
+    /// Inverse by Gauss-Jordan elimination, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Matrix4x4> {
+        let mut m = self.data;
+        let mut inv = Matrix4x4::identity().data;
+
+        for col in 0..4 {
+            // Partial pivoting on the largest remaining entry of the column
+            let pivot = (col..4).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+            if m[pivot][col].abs() < f32::EPSILON {
+                return None;
+            }
+            m.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let div = m[col][col];
+            for j in 0..4 {
+                m[col][j] /= div;
+                inv[col][j] /= div;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let factor = m[row][col];
+                    for j in 0..4 {
+                        m[row][j] -= factor * m[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+
+        Some(Matrix4x4 { data: inv })
+    }
+
+    /// Applies the matrix to a position (`w = 1`).
+    pub fn transform_point(&self, p: &Point) -> Point {
+        *self * *p
+    }
+
     pub fn rotate_z(angle: f32) -> Self {
         let cos = angle.cos();
         let sin = angle.sin();
@@ -73,12 +157,7 @@ impl Matrix4x4 {
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct Point {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
-}
+pub type Point = super::math::Vec3;
 
 pub struct Edge {
     pub start: usize, // Index of first point
@@ -94,12 +173,37 @@ pub struct Triangle {
 pub struct Object {
     pub id: usize,
     pub points: Vec<Point>,
+    /// Optional color per point, overriding `color` and interpolated across edges and triangles.
+    pub vertex_colors: Option<Vec<Color>>,
     pub original_points: Vec<Point>,
     pub edges: Vec<Edge>,
     pub triangles: Vec<Triangle>,
-    pub shader: super::shader::Shader,
+    /// Texture coordinates of the corners of each triangle, so that points on
+    /// a seam can map to different parts of the texture in each face.
+    pub uvs: Option<Vec<[Vec2; 3]>>,
+    pub texture: Option<Rc<Texture>>,
+    pub shader: Box<dyn super::shader::Shader>,
+    pub material: super::shader::Material,
+    /// Base color of the whole object.
+    pub color: Color,
+    /// Color of the wireframe; follows the point colors when unset.
+    pub edge_color: Option<Color>,
+    pub line_style: super::render::LineStyle,
+    pub render_mode: super::render::RenderMode,
+    pub cull_mode: super::render::CullMode,
+    pub front_face: super::render::FrontFace,
+    pub blend_mode: super::shader::BlendMode,
+    pub shading: super::shader::ShadingMode,
+    /// Accumulated world matrix taking `original_points` to `points`.
     pub transform: Matrix4x4,
+    /// Orientation around the object's own center.
+    pub rotation: Quaternion,
+    /// Scale factor along each of the object's own axes.
+    pub scale: Point,
+    /// Current world position of the object's center.
     pub center: Point,
+    /// Center of the object in `original_points`.
+    pub pivot: Point,
 }
 
 impl Object {
@@ -107,6 +211,10 @@ impl Object {
         let mut points = vec![];
         let mut edges = vec![];
         let mut triangles = vec![];
+        let mut uvs = vec![];
+
+        // Equirectangular mapping: u goes once around the poles, v from north to south
+        let uv = |i: f32, j: f32| Vec2::new(i / res, j / res);
 
         // Add north pole point
         points.push(Point {
@@ -149,9 +257,11 @@ impl Object {
             // Add triangle
             triangles.push(Triangle {
                 a: 0,
-                b: current,
-                c: next,
+                b: next,
+                c: current,
             });
+            let i = i as f32;
+            uvs.push([uv(i + 0.5, 0.0), uv(i + 1.0, 1.0), uv(i, 1.0)]);
         }
 
         // Connect intermediate rings
@@ -185,6 +295,11 @@ impl Object {
                     b: below_next,
                     c: below,
                 });
+
+                // The last column ends at u = 1 rather than wrapping back to 0
+                let (i, j) = (i as f32, j as f32 + 1.0);
+                uvs.push([uv(i, j), uv(i + 1.0, j), uv(i, j + 1.0)]);
+                uvs.push([uv(i + 1.0, j), uv(i + 1.0, j + 1.0), uv(i, j + 1.0)]);
             }
         }
 
@@ -204,9 +319,11 @@ impl Object {
             // Add triangle
             triangles.push(Triangle {
                 a: south_pole_idx,
-                b: next,
-                c: current,
+                b: current,
+                c: next,
             });
+            let (i, j) = (i as f32, res - 1.0);
+            uvs.push([uv(i + 0.5, res), uv(i, j), uv(i + 1.0, j)]);
         }
 
         // Store original points and center
@@ -216,12 +333,27 @@ impl Object {
         Self {
             id,
             points,
+            vertex_colors: None,
             original_points,
             edges,
             triangles,
-            shader: super::shader::Shader::new(),
+            uvs: Some(uvs),
+            texture: None,
+            shader: Box::new(super::shader::StandardShader),
+            material: super::shader::Material::default(),
+            color: Color::WHITE,
+            edge_color: None,
+            line_style: super::render::LineStyle::default(),
+            render_mode: super::render::RenderMode::default(),
+            cull_mode: super::render::CullMode::default(),
+            front_face: super::render::FrontFace::default(),
+            blend_mode: super::shader::BlendMode::default(),
+            shading: super::shader::ShadingMode::default(),
             transform: Matrix4x4::identity(),
+            rotation: Quaternion::identity(),
+            scale: Point::new(1.0, 1.0, 1.0),
             center,
+            pivot: center,
         }
     }
 
@@ -229,7 +361,7 @@ impl Object {
         let half_size = size / 2.0;
 
         // Define the 8 vertices of the cube
-        let mut points = vec![
+        let points = vec![
             // Front face
             Point {
                 x: pos.x - half_size,
@@ -315,62 +447,304 @@ impl Object {
             Triangle { a: 4, b: 1, c: 0 },
         ];
 
+        // Map each face onto the whole texture, upright when seen from outside
+        let uvs = triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = [points[t.a], points[t.b], points[t.c]];
+                let normal = (b - a).cross(c - a).normalize();
+                let up = if normal.y.abs() > 0.5 {
+                    Point::new(0.0, 0.0, 1.0)
+                } else {
+                    Point::new(0.0, 1.0, 0.0)
+                };
+                let right = normal.cross(up);
+                [a, b, c].map(|p| {
+                    let offset = p - pos;
+                    Vec2::new(0.5 + offset.dot(right) / size, 0.5 - offset.dot(up) / size)
+                })
+            })
+            .collect();
+
         // Store original points and center
         let original_points = points.clone();
 
         Self {
             id,
             points,
+            vertex_colors: None,
             original_points,
             edges,
             triangles,
-            shader: super::shader::Shader::new(),
+            uvs: Some(uvs),
+            texture: None,
+            shader: Box::new(super::shader::StandardShader),
+            material: super::shader::Material::default(),
+            color: Color::WHITE,
+            edge_color: None,
+            line_style: super::render::LineStyle::default(),
+            render_mode: super::render::RenderMode::default(),
+            cull_mode: super::render::CullMode::default(),
+            front_face: super::render::FrontFace::default(),
+            blend_mode: super::shader::BlendMode::default(),
+            shading: super::shader::ShadingMode::default(),
             transform: Matrix4x4::identity(),
+            rotation: Quaternion::identity(),
+            scale: Point::new(1.0, 1.0, 1.0),
             center: pos,
+            pivot: pos,
         }
     }
 
     pub fn rotate_x(&mut self, angle: f32) {
-        self.transform = self.transform.multiply(&Matrix4x4::rotate_x(angle));
+        self.rotate_local(
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            angle,
+        );
         self.apply_transform();
     }
 
     pub fn rotate_y(&mut self, angle: f32) {
-        self.transform = self.transform.multiply(&Matrix4x4::rotate_y(angle));
+        self.rotate_local(
+            Point {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            angle,
+        );
         self.apply_transform();
     }
 
     pub fn rotate_z(&mut self, angle: f32) {
-        self.transform = self.transform.multiply(&Matrix4x4::rotate_z(angle));
+        self.rotate_local(
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            angle,
+        );
         self.apply_transform();
     }
 
+    /// Rotates around one of the object's own axes. The quaternion is renormalised
+    /// every time so the orientation cannot drift however many steps are taken.
+    fn rotate_local(&mut self, axis: Point, angle: f32) {
+        let step = Quaternion::from_axis_angle(axis, angle);
+        self.rotation = self.rotation.multiply(&step).normalize();
+    }
+
+    /// Replaces the orientation with Euler angles in radians, see `Matrix4x4::from_euler`.
+    pub fn set_rotation(&mut self, x: f32, y: f32, z: f32) {
+        self.rotation = Quaternion::from_euler(x, y, z);
+        self.apply_transform();
+    }
+
+    pub fn set_orientation(&mut self, rotation: Quaternion) {
+        self.rotation = rotation.normalize();
+        self.apply_transform();
+    }
+
+    pub fn translate(&mut self, x: f32, y: f32, z: f32) {
+        self.center += Point { x, y, z };
+        self.apply_transform();
+    }
+
+    pub fn set_position(&mut self, pos: Point) {
+        self.center = pos;
+        self.apply_transform();
+    }
+
+    /// Multiplies the current scale along the object's own axes.
+    pub fn scale(&mut self, x: f32, y: f32, z: f32) {
+        self.scale.x *= x;
+        self.scale.y *= y;
+        self.scale.z *= z;
+        self.apply_transform();
+    }
+
+    /// Replaces the world matrix. Position, rotation and scale are recovered from
+    /// it, so the matrix should not contain shear or projection. A mirroring
+    /// matrix, with a negative determinant, becomes a negative scale along X.
+    pub fn set_transform(&mut self, transform: Matrix4x4) {
+        self.center = transform * self.pivot;
+
+        // The columns of the linear part are the scaled rotation axes
+        let d = &transform.data;
+        let mut axes = [0, 1, 2].map(|col| Point::new(d[0][col], d[1][col], d[2][col]));
+        let mut scale = axes.map(|axis| axis.length());
+
+        // Flip X so what remains is a rotation rather than a reflection
+        if axes[0].dot(axes[1].cross(axes[2])) < 0.0 {
+            axes[0] = axes[0] * -1.0;
+            scale[0] = -scale[0];
+        }
+
+        let mut rotation = Matrix4x4::identity();
+        for (col, axis) in axes.iter().enumerate() {
+            let axis = axis.normalize();
+            rotation.data[0][col] = axis.x;
+            rotation.data[1][col] = axis.y;
+            rotation.data[2][col] = axis.z;
+        }
+
+        self.rotation = Quaternion::from_matrix(&rotation);
+        self.scale = Point {
+            x: scale[0],
+            y: scale[1],
+            z: scale[2],
+        };
+        self.apply_transform();
+    }
+
+    /// Whether what is behind the object shows through it, so it has to be
+    /// drawn after the opaque objects.
+    pub fn is_translucent(&self) -> bool {
+        self.blend_mode != super::shader::BlendMode::Alpha
+            || self.color.a < 255
+            || self
+                .vertex_colors
+                .as_ref()
+                .is_some_and(|colors| colors.iter().any(|c| c.a < 255))
+    }
+
+    /// Texture coordinates of the corners of triangle `index`, all zero without `uvs`.
+    pub fn triangle_uvs(&self, index: usize) -> [Vec2; 3] {
+        match &self.uvs {
+            Some(uvs) if index < uvs.len() => uvs[index],
+            _ => [Vec2::new(0.0, 0.0); 3],
+        }
+    }
+
+    /// Color of the point at `index`, from `vertex_colors` if present.
+    pub fn vertex_color(&self, index: usize) -> Color {
+        match &self.vertex_colors {
+            Some(colors) if index < colors.len() => colors[index],
+            _ => self.color,
+        }
+    }
+
+    /// Unit normal of a triangle; triangles are wound counter-clockwise around it.
+    pub fn face_normal(&self, triangle: &Triangle) -> Point {
+        let a = self.points[triangle.a];
+        let b = self.points[triangle.b];
+        let c = self.points[triangle.c];
+        (b - a).cross(c - a).normalize()
+    }
+
+    /// Per-point normals, averaged from the faces around each point and weighted by area.
+    pub fn vertex_normals(&self) -> Vec<Point> {
+        let mut normals = vec![Point::default(); self.points.len()];
+
+        for triangle in &self.triangles {
+            let a = self.points[triangle.a];
+            let b = self.points[triangle.b];
+            let c = self.points[triangle.c];
+            let n = (b - a).cross(c - a);
+
+            for idx in [triangle.a, triangle.b, triangle.c] {
+                normals[idx] += n;
+            }
+        }
+
+        normals.iter().map(|n| n.normalize()).collect()
+    }
+
     fn apply_transform(&mut self) {
-        // Reset points to original positions
-        self.points = self.original_points.clone();
-
-        // Apply the transformation matrix to each point
-        for i in 0..self.points.len() {
-            // Translate point to origin
-            let mut x = self.points[i].x - self.center.x;
-            let mut y = self.points[i].y - self.center.y;
-            let mut z = self.points[i].z - self.center.z;
-
-            // Apply the transformation matrix
-            let new_x = self.transform.data[0][0] * x
-                + self.transform.data[0][1] * y
-                + self.transform.data[0][2] * z;
-            let new_y = self.transform.data[1][0] * x
-                + self.transform.data[1][1] * y
-                + self.transform.data[1][2] * z;
-            let new_z = self.transform.data[2][0] * x
-                + self.transform.data[2][1] * y
-                + self.transform.data[2][2] * z;
-
-            // Translate back
-            self.points[i].x = new_x + self.center.x;
-            self.points[i].y = new_y + self.center.y;
-            self.points[i].z = new_z + self.center.z;
+        // Move the pivot to the origin, scale, rotate, then move to the center
+        self.transform = Matrix4x4::translate(self.center.x, self.center.y, self.center.z)
+            .multiply(&self.rotation.to_matrix())
+            .multiply(&Matrix4x4::scale(self.scale.x, self.scale.y, self.scale.z))
+            .multiply(&Matrix4x4::translate(
+                -self.pivot.x,
+                -self.pivot.y,
+                -self.pivot.z,
+            ));
+
+        self.points = self
+            .original_points
+            .iter()
+            .map(|p| self.transform * *p)
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Point, b: Point) {
+        assert!((a - b).length() < 1e-3, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn set_transform_recovers_position_rotation_and_scale() {
+        let mut cube = Object::new_cube(0, 2.0, Point::new(1.0, 2.0, 3.0));
+        let transform = Matrix4x4::translate(5.0, -1.0, 2.0)
+            .multiply(&Matrix4x4::from_euler(0.3, -0.7, 1.1))
+            .multiply(&Matrix4x4::scale(2.0, 0.5, 3.0));
+        cube.set_transform(transform);
+
+        assert_close(cube.scale, Point::new(2.0, 0.5, 3.0));
+        for (point, original) in cube.points.iter().zip(&cube.original_points) {
+            assert_close(*point, transform * *original);
         }
     }
+
+    #[test]
+    fn set_transform_keeps_reflections() {
+        let mut cube = Object::new_cube(0, 2.0, Point::default());
+        let transform = Matrix4x4::rotate_y(0.5).multiply(&Matrix4x4::scale(1.0, -2.0, 1.0));
+        cube.set_transform(transform);
+
+        assert_close(cube.scale, Point::new(-1.0, 2.0, 1.0));
+        for (point, original) in cube.points.iter().zip(&cube.original_points) {
+            assert_close(*point, transform * *original);
+        }
+    }
+
+    #[test]
+    fn set_transform_keeps_a_mirrored_x_axis() {
+        let mut cube = Object::new_cube(0, 2.0, Point::new(1.0, -2.0, 4.0));
+        let transform = Matrix4x4::translate(3.0, 0.0, -1.0)
+            .multiply(&Matrix4x4::from_euler(0.2, 0.4, -0.6))
+            .multiply(&Matrix4x4::scale(-1.0, 1.0, 1.0));
+        cube.set_transform(transform);
+        assert_close(cube.scale, Point::new(-1.0, 1.0, 1.0));
+
+        // Moving the object rebuilds the points from position, rotation and scale
+        cube.translate(0.0, 0.0, 0.0);
+        for (point, original) in cube.points.iter().zip(&cube.original_points) {
+            assert_close(*point, transform * *original);
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let m = Matrix4x4::translate(4.0, -2.0, 7.0)
+            .multiply(&Matrix4x4::from_euler(0.5, -1.2, 2.0))
+            .multiply(&Matrix4x4::scale(2.0, 0.25, -3.0));
+        let inverse = m.inverse().unwrap();
+        let identity = Matrix4x4::identity();
+        for product in [m.multiply(&inverse), inverse.multiply(&m)] {
+            for (row, expected) in product.data.iter().zip(identity.data) {
+                for (v, e) in row.iter().zip(expected) {
+                    assert!((v - e).abs() < 1e-5, "{:?}", product.data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert!(Matrix4x4::scale(1.0, 0.0, 1.0).inverse().is_none());
+        let mut m = Matrix4x4::identity();
+        m.data[2] = m.data[1];
+        assert!(m.inverse().is_none());
+    }
 }
@@ -0,0 +1,146 @@
+use super::shader::Color;
+
+/// Layout of a packed pixel in a display buffer, from the most significant bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PixelFormat {
+    /// `0xAARRGGBB`, what minifb expects. Its alpha byte is ignored there.
+    #[default]
+    Argb8888,
+    /// `0xAABBGGRR`, bytes in RGBA order on little-endian machines.
+    Abgr8888,
+    /// 5 bits of red, 6 of green and 5 of blue in the low 16 bits. Always opaque.
+    Rgb565,
+    /// Luma in the low 8 bits. Always opaque.
+    Gray8,
+}
+
+impl PixelFormat {
+    pub fn pack(self, color: Color) -> u32 {
+        let (r, g, b, a) = (
+            color.r as u32,
+            color.g as u32,
+            color.b as u32,
+            color.a as u32,
+        );
+        match self {
+            PixelFormat::Argb8888 => (a << 24) | (r << 16) | (g << 8) | b,
+            PixelFormat::Abgr8888 => (a << 24) | (b << 16) | (g << 8) | r,
+            PixelFormat::Rgb565 => ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3),
+            PixelFormat::Gray8 => luma(color) as u32,
+        }
+    }
+
+    pub fn unpack(self, pixel: u32) -> Color {
+        let byte = |shift: u32| ((pixel >> shift) & 0xFF) as u8;
+        match self {
+            PixelFormat::Argb8888 => Color {
+                r: byte(16),
+                g: byte(8),
+                b: byte(0),
+                a: byte(24),
+            },
+            PixelFormat::Abgr8888 => Color {
+                r: byte(0),
+                g: byte(8),
+                b: byte(16),
+                a: byte(24),
+            },
+            PixelFormat::Rgb565 => {
+                // Replicate the high bits into the low ones so white stays white
+                let r = ((pixel >> 11) & 0x1F) as u8;
+                let g = ((pixel >> 5) & 0x3F) as u8;
+                let b = (pixel & 0x1F) as u8;
+                Color {
+                    r: (r << 3) | (r >> 2),
+                    g: (g << 2) | (g >> 4),
+                    b: (b << 3) | (b >> 2),
+                    a: 255,
+                }
+            }
+            PixelFormat::Gray8 => {
+                let v = byte(0);
+                Color {
+                    r: v,
+                    g: v,
+                    b: v,
+                    a: 255,
+                }
+            }
+        }
+    }
+}
+
+// Rec. 601 luma of the gamma-encoded channels
+fn luma(color: Color) -> u8 {
+    (0.299 * color.r as f32 + 0.587 * color.g as f32 + 0.114 * color.b as f32)
+        .round()
+        .clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    #[test]
+    fn eight_bit_formats_round_trip_exactly() {
+        let colors = [
+            color(255, 0, 0, 255),
+            color(0, 255, 0, 128),
+            color(0, 0, 255, 0),
+            color(12, 34, 56, 78),
+        ];
+        for format in [PixelFormat::Argb8888, PixelFormat::Abgr8888] {
+            for c in colors {
+                assert_eq!(format.unpack(format.pack(c)), c, "{format:?}");
+            }
+        }
+        let red = color(255, 0, 0, 255);
+        assert_eq!(PixelFormat::Argb8888.pack(red), 0xFFFF_0000);
+        assert_eq!(PixelFormat::Abgr8888.pack(red), 0xFF00_00FF);
+    }
+
+    #[test]
+    fn rgb565_keeps_five_and_six_bit_values() {
+        // Channels whose low bits repeat their high ones are exactly what
+        // unpacking produces, so they survive the trip unchanged
+        let widen5 = |v: u8| (v << 3) | (v >> 2);
+        let widen6 = |v: u8| (v << 2) | (v >> 4);
+        for r in 0..32 {
+            for g in 0..64 {
+                for b in 0..32 {
+                    let c = color(widen5(r), widen6(g), widen5(b), 255);
+                    let packed = PixelFormat::Rgb565.pack(c);
+                    assert_eq!(packed, ((r as u32) << 11) | ((g as u32) << 5) | b as u32);
+                    assert_eq!(PixelFormat::Rgb565.unpack(packed), c);
+                }
+            }
+        }
+
+        // Other values lose their low bits, and alpha is dropped
+        let c = PixelFormat::Rgb565.unpack(PixelFormat::Rgb565.pack(color(100, 100, 100, 7)));
+        assert_eq!(c, color(99, 101, 99, 255));
+        assert_eq!(PixelFormat::Rgb565.pack(Color::WHITE), 0xFFFF);
+    }
+
+    #[test]
+    fn gray8_stores_rec601_luma() {
+        let cases = [
+            (color(255, 0, 0, 255), 76),
+            (color(0, 255, 0, 255), 150),
+            (color(0, 0, 255, 255), 29),
+            (color(255, 255, 255, 0), 255),
+            (color(10, 200, 30, 255), 124),
+        ];
+        for (c, luma) in cases {
+            assert_eq!(PixelFormat::Gray8.pack(c), luma, "{c:?}");
+            assert_eq!(
+                PixelFormat::Gray8.unpack(luma),
+                color(luma as u8, luma as u8, luma as u8, 255)
+            );
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use crate::image::{GifWriter, Image, ImageError, ImageFormat, Y4mWriter};
+
+/// Captures the frames presented by `Space::update`.
+///
+/// While a recorder is attached, `Space::delta_time` reports its fixed frame
+/// time instead of the wall clock, so animations driven by it come out the
+/// same however long each frame takes to render.
+pub struct Recorder {
+    fps: u32,
+    dither: bool,
+    frames: usize,
+    output: Output,
+}
+
+enum Output {
+    Images {
+        directory: PathBuf,
+        format: ImageFormat,
+    },
+    Y4m {
+        path: PathBuf,
+        writer: Option<Y4mWriter<BufWriter<File>>>,
+    },
+    Gif {
+        path: PathBuf,
+        writer: Option<GifWriter<BufWriter<File>>>,
+    },
+}
+
+impl Recorder {
+    /// Saves every frame as `frame-NNNN` in `directory`, creating it if needed.
+    ///
+    /// Like the other constructors, fails if `fps` is 0.
+    pub fn images(
+        directory: impl Into<PathBuf>,
+        format: ImageFormat,
+        fps: u32,
+    ) -> Result<Self, ImageError> {
+        Self::new(
+            fps,
+            Output::Images {
+                directory: directory.into(),
+                format,
+            },
+        )
+    }
+
+    /// Writes a YUV4MPEG2 video, e.g. for `ffmpeg -i video.y4m video.mp4`.
+    pub fn y4m(path: impl Into<PathBuf>, fps: u32) -> Result<Self, ImageError> {
+        Self::new(
+            fps,
+            Output::Y4m {
+                path: path.into(),
+                writer: None,
+            },
+        )
+    }
+
+    /// Writes an animated GIF. Frame delays are whole hundredths of a second
+    /// and many viewers slow down delays under two, so 50 fps or less plays
+    /// back at the recorded speed.
+    pub fn gif(path: impl Into<PathBuf>, fps: u32) -> Result<Self, ImageError> {
+        Self::new(
+            fps,
+            Output::Gif {
+                path: path.into(),
+                writer: None,
+            },
+        )
+    }
+
+    fn new(fps: u32, output: Output) -> Result<Self, ImageError> {
+        if fps == 0 {
+            return Err(ImageError::Invalid("recording at 0 fps".into()));
+        }
+        Ok(Recorder {
+            fps,
+            dither: false,
+            frames: 0,
+            output,
+        })
+    }
+
+    /// Dithers GIF frames that have more colors than fit in their palette.
+    pub fn with_dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Simulated seconds between frames.
+    pub fn frame_time(&self) -> f32 {
+        1.0 / self.fps as f32
+    }
+
+    /// Number of frames captured so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Appends a frame. Files are created when the first frame arrives.
+    pub fn capture(&mut self, image: &Image) -> Result<(), ImageError> {
+        let create = |path: &PathBuf| File::create(path).map(BufWriter::new);
+        match &mut self.output {
+            Output::Images { directory, format } => {
+                if self.frames == 0 {
+                    std::fs::create_dir_all(&*directory)?;
+                }
+                let name = format!("frame-{:04}.{}", self.frames, format.extension());
+                image.save_as(directory.join(name), *format)?;
+            }
+            Output::Y4m { path, writer } => {
+                if writer.is_none() {
+                    let file = create(path)?;
+                    *writer = Some(Y4mWriter::new(file, image.width, image.height, self.fps)?);
+                }
+                if let Some(writer) = writer {
+                    writer.write_frame(image)?;
+                }
+            }
+            Output::Gif { path, writer } => {
+                if writer.is_none() {
+                    let file = create(path)?;
+                    *writer = Some(GifWriter::new(
+                        file,
+                        image.width,
+                        image.height,
+                        self.dither,
+                    )?);
+                }
+                // Rounded so the total time stays on track, e.g. 2, 1, 2, 2,
+                // 1, 2 hundredths at 60 fps
+                let at = |frame: usize| (frame as f64 * 100.0 / self.fps as f64).round() as u64;
+                let delay = (at(self.frames + 1) - at(self.frames)) as u16;
+                if let Some(writer) = writer {
+                    writer.write_frame(image, delay)?;
+                }
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Completes the files and returns the number of frames recorded.
+    pub fn finish(self) -> Result<usize, ImageError> {
+        match self.output {
+            Output::Images { .. } => {}
+            Output::Y4m { writer, .. } => {
+                if let Some(writer) = writer {
+                    writer.finish()?;
+                }
+            }
+            Output::Gif { writer, .. } => {
+                if let Some(writer) = writer {
+                    writer.finish()?;
+                }
+            }
+        }
+        Ok(self.frames)
+    }
+}
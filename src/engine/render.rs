@@ -0,0 +1,1095 @@
+use super::clip::{self, ClipVertex};
+use super::math::Vec4;
+use super::object::{Object, Point};
+use super::pixel::PixelFormat;
+use super::shader::{
+    BlendMode, Color, Fragment, UV_VARYINGS, Uniforms, VIEW_DEPTH_VARYING, Varyings, Vertex,
+    VertexOutput,
+};
+
+/// How an object's geometry is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// Points and edges only.
+    #[default]
+    Wireframe,
+    /// Filled triangles only.
+    Solid,
+    /// Filled triangles with the wireframe drawn on top.
+    SolidWireframe,
+    /// Only the edges that are not hidden behind faces. Triangles are filled
+    /// into the depth buffer without color.
+    HiddenLine,
+}
+
+impl RenderMode {
+    /// Whether triangles are rasterized, either in color or into depth only.
+    pub fn fills(self) -> bool {
+        !matches!(self, RenderMode::Wireframe)
+    }
+
+    /// Whether rasterized triangles write color.
+    pub fn colors_faces(self) -> bool {
+        matches!(self, RenderMode::Solid | RenderMode::SolidWireframe)
+    }
+
+    pub fn draws_edges(self) -> bool {
+        !matches!(self, RenderMode::Solid)
+    }
+}
+
+/// Which triangles are skipped when filling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CullMode {
+    /// Draw every triangle.
+    None,
+    /// Skip triangles facing away from the camera.
+    #[default]
+    Back,
+    /// Skip triangles facing the camera, e.g. to see the inside of a room.
+    Front,
+}
+
+/// Winding, as seen on screen, of triangles that face the camera.
+///
+/// The built-in meshes have outward normals `(b - a) x (c - a)`, which appear
+/// clockwise through the engine's left-handed camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrontFace {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+/// How an object's edges are stroked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineStyle {
+    /// Thickness in pixels.
+    pub width: f32,
+    /// Smooths the edges of lines with coverage-based alpha.
+    pub antialiased: bool,
+    /// Alternating on and off lengths in pixels, starting with on. Empty is a solid line.
+    pub dash: Vec<f32>,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle {
+            width: 1.0,
+            antialiased: false,
+            dash: Vec::new(),
+        }
+    }
+}
+
+impl LineStyle {
+    pub fn dashed(on: f32, off: f32) -> Self {
+        LineStyle::default().with_dash(vec![on, off])
+    }
+
+    pub fn dotted(spacing: f32) -> Self {
+        LineStyle::default().with_dash(vec![1.0, spacing])
+    }
+
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_antialiasing(mut self, antialiased: bool) -> Self {
+        self.antialiased = antialiased;
+        self
+    }
+
+    pub fn with_dash(mut self, dash: Vec<f32>) -> Self {
+        self.dash = dash;
+        self
+    }
+
+    /// Whether the pattern is drawing at `distance` pixels along the line.
+    pub fn is_on(&self, distance: f32) -> bool {
+        let period: f32 = self.dash.iter().sum();
+        if period <= 0.0 {
+            return true;
+        }
+
+        let mut position = distance % period;
+        for (i, length) in self.dash.iter().enumerate() {
+            if position < *length {
+                return i % 2 == 0;
+            }
+            position -= length;
+        }
+        true
+    }
+}
+
+/// How the edges of filled geometry are smoothed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AntiAliasing {
+    /// One sample at the center of each pixel.
+    #[default]
+    None,
+    /// Renders at twice the resolution along each axis and averages every 2x2 block.
+    Supersample2x,
+    /// Renders at four times the resolution along each axis and averages every 4x4 block.
+    Supersample4x,
+    /// Tests coverage and depth at 2 samples per pixel but shades each pixel once.
+    Msaa2x,
+    /// Tests coverage and depth at 4 samples per pixel but shades each pixel once.
+    Msaa4x,
+}
+
+impl AntiAliasing {
+    /// Internal pixels per output pixel along each axis.
+    pub fn scale(self) -> usize {
+        match self {
+            AntiAliasing::Supersample2x => 2,
+            AntiAliasing::Supersample4x => 4,
+            _ => 1,
+        }
+    }
+
+    /// Positions of the coverage samples within a pixel, from its top-left corner.
+    pub fn sample_positions(self) -> &'static [(f32, f32)] {
+        match self {
+            AntiAliasing::Msaa2x => &[(0.75, 0.75), (0.25, 0.25)],
+            // Rotated grid, so near-horizontal and near-vertical edges get 4 levels
+            AntiAliasing::Msaa4x => &[
+                (0.375, 0.125),
+                (0.875, 0.375),
+                (0.125, 0.625),
+                (0.625, 0.875),
+            ],
+            _ => &[(0.5, 0.5)],
+        }
+    }
+}
+
+/// How translucent faces are composited once the opaque objects are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transparency {
+    /// Translucent triangles of all objects are sorted back to front by view
+    /// depth and blended in that order.
+    #[default]
+    Sorted,
+    /// Weighted blended order-independent transparency. Intersecting meshes
+    /// need no sorting, but the result is an approximation and faces always
+    /// blend as straight alpha.
+    WeightedBlended,
+}
+
+/// Where `fill_triangle` writes the fragments it shades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FragmentOutput {
+    /// Blend into the color samples and write depth.
+    Blend(BlendMode),
+    /// Blend into the color samples, leaving depth to the surfaces behind.
+    BlendNoDepth(BlendMode),
+    /// Accumulate into the weighted blended transparency buffers.
+    WeightedBlended,
+}
+
+/// Perspective-correct barycentric weights of a pixel, and of the pixels to
+/// its right and below for screen-space derivatives.
+#[derive(Debug, Clone, Copy)]
+pub struct Barycentrics {
+    pub center: [f32; 3],
+    pub right: [f32; 3],
+    pub below: [f32; 3],
+}
+
+// Depth offset, a few steps of f32 near 1, that faces with edges drawn over
+// them are pushed back by on top of their slope
+const MIN_DEPTH_OFFSET: f32 = 4.0 * f32::EPSILON;
+
+/// Color and depth planes that every object of a frame is rendered into.
+///
+/// `width` and `height` are the internal resolution, which is larger than the
+/// output when supersampling. Each pixel holds `samples` consecutive color and
+/// depth samples.
+pub struct RenderTarget {
+    pub width: usize,
+    pub height: usize,
+    pub antialiasing: AntiAliasing,
+    pub samples: usize,
+    /// Blends in linear light instead of directly on the sRGB values.
+    pub linear_blending: bool,
+    pub transparency: Transparency,
+    pub color: Vec<Color>,
+    pub depth: Vec<f32>,
+    // Weighted sums of premultiplied color and alpha, and the product of
+    // `1 - alpha`, per sample. Only allocated once weighted blending is used.
+    accum: Vec<[f32; 4]>,
+    revealage: Vec<f32>,
+}
+
+impl RenderTarget {
+    /// Creates a target producing `width` by `height` output pixels.
+    pub fn new(width: usize, height: usize, antialiasing: AntiAliasing) -> Self {
+        let scale = antialiasing.scale();
+        let (width, height) = (width * scale, height * scale);
+        let samples = antialiasing.sample_positions().len();
+        RenderTarget {
+            width,
+            height,
+            antialiasing,
+            samples,
+            linear_blending: false,
+            transparency: Transparency::default(),
+            color: vec![Color::TRANSPARENT; width * height * samples],
+            depth: vec![f32::INFINITY; width * height * samples],
+            accum: Vec::new(),
+            revealage: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.color.fill(Color::TRANSPARENT);
+        self.depth.fill(f32::INFINITY);
+        self.accum.fill([0.0; 4]);
+        self.revealage.fill(1.0);
+    }
+
+    // Adds a translucent fragment at view depth `distance` to sample `s`,
+    // weighted so that nearer and more opaque fragments dominate
+    fn accumulate(&mut self, s: usize, color: Color, distance: f32) {
+        if self.accum.is_empty() {
+            self.accum = vec![[0.0; 4]; self.color.len()];
+            self.revealage = vec![1.0; self.color.len()];
+        }
+
+        // Depth weight from McGuire and Bavoil, "Weighted Blended Order-Independent Transparency"
+        let alpha = color.a as f32 / 255.0;
+        let d = distance.abs();
+        let weight =
+            alpha * (10.0 / (1e-5 + (d / 5.0).powi(2) + (d / 200.0).powi(6))).clamp(1e-2, 3e3);
+
+        let [r, g, b] = color.scaled_rgb(1.0);
+        let sum = &mut self.accum[s];
+        sum[0] += r * weight;
+        sum[1] += g * weight;
+        sum[2] += b * weight;
+        sum[3] += weight;
+        self.revealage[s] *= 1.0 - alpha;
+    }
+
+    /// Composites what was accumulated by weighted blending over the color
+    /// samples.
+    pub fn resolve_weighted(&mut self) {
+        for s in 0..self.accum.len() {
+            let [r, g, b, weight] = self.accum[s];
+            let alpha = 1.0 - self.revealage[s];
+            if weight <= 0.0 || alpha <= 0.0 {
+                continue;
+            }
+
+            let channel = |v: f32| (v / weight * 255.0).round().clamp(0.0, 255.0) as u8;
+            let average = Color {
+                r: channel(r),
+                g: channel(g),
+                b: channel(b),
+                a: (alpha * 255.0).round() as u8,
+            };
+            self.color[s] = BlendMode::Alpha.blend(average, self.color[s], self.linear_blending);
+        }
+    }
+
+    /// The resolved pixels as an image, e.g. for saving with `Image::save`.
+    pub fn to_image(&self) -> crate::image::Image {
+        let scale = self.antialiasing.scale();
+        crate::image::Image::new(self.width / scale, self.height / scale, self.resolve())
+    }
+
+    /// Averages the samples and supersampled pixels down to the output resolution.
+    pub fn resolve(&self) -> Vec<Color> {
+        let scale = self.antialiasing.scale();
+        let (width, height) = (self.width / scale, self.height / scale);
+        let count = (scale * scale * self.samples) as f32;
+
+        let mut output = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                // Weight by alpha so transparent samples do not darken the result
+                let mut sum = [0.0f32; 4];
+                for sy in 0..scale {
+                    let row = (y * scale + sy) * self.width + x * scale;
+                    let start = row * self.samples;
+                    let end = start + scale * self.samples;
+                    for sample in &self.color[start..end] {
+                        let a = sample.a as f32;
+                        sum[0] += sample.r as f32 * a;
+                        sum[1] += sample.g as f32 * a;
+                        sum[2] += sample.b as f32 * a;
+                        sum[3] += a;
+                    }
+                }
+
+                if sum[3] <= 0.0 {
+                    output.push(Color::TRANSPARENT);
+                    continue;
+                }
+                let channel = |v: f32| (v / sum[3]).round().clamp(0.0, 255.0) as u8;
+                output.push(Color {
+                    r: channel(sum[0]),
+                    g: channel(sum[1]),
+                    b: channel(sum[2]),
+                    a: (sum[3] / count).round() as u8,
+                });
+            }
+        }
+        output
+    }
+
+    // Blends `color` into every sample of pixel `idx` that is further than
+    // `z`, optionally without claiming the depth, for partially covered pixels
+    fn composite(&mut self, idx: usize, z: f32, color: Color, mode: BlendMode, write_depth: bool) {
+        for s in idx * self.samples..(idx + 1) * self.samples {
+            self.composite_sample(s, z, color, mode, write_depth);
+        }
+    }
+
+    fn composite_sample(
+        &mut self,
+        s: usize,
+        z: f32,
+        color: Color,
+        mode: BlendMode,
+        write_depth: bool,
+    ) {
+        if s < self.color.len() && z < self.depth[s] {
+            self.color[s] = mode.blend(color, self.color[s], self.linear_blending);
+            if write_depth {
+                self.depth[s] = z;
+            }
+        }
+    }
+}
+
+/// Draws `object` into `target`, depth testing against everything already in it.
+pub fn render_object(
+    object: &Object,
+    cam: &super::space::Camera,
+    lights: &[super::light::Light],
+    target: &mut RenderTarget,
+) {
+    let prepared = Prepared::new(object, cam, lights);
+    if object.render_mode.fills() {
+        for index in 0..object.triangles.len() {
+            prepared.fill(index, FragmentOutput::Blend(object.blend_mode), target);
+        }
+    }
+    if object.render_mode.draws_edges() {
+        prepared.draw_edges(target);
+    }
+}
+
+/// Draws a whole frame into `target`.
+///
+/// Opaque objects are drawn first, in order. The faces of translucent objects
+/// follow as chosen by `target.transparency`, then their edges.
+pub fn render_scene<'a>(
+    objects: impl IntoIterator<Item = &'a Object>,
+    cam: &super::space::Camera,
+    lights: &[super::light::Light],
+    target: &mut RenderTarget,
+) {
+    let (translucent, opaque): (Vec<&Object>, Vec<&Object>) =
+        objects.into_iter().partition(|o| o.is_translucent());
+
+    for object in opaque {
+        render_object(object, cam, lights, target);
+    }
+
+    let prepared: Vec<Prepared> = translucent
+        .into_iter()
+        .map(|object| Prepared::new(object, cam, lights))
+        .collect();
+
+    // Hidden-line objects only put their faces into the depth buffer
+    for p in &prepared {
+        let mode = p.object.render_mode;
+        if mode.fills() && !mode.colors_faces() {
+            for index in 0..p.object.triangles.len() {
+                p.fill(index, FragmentOutput::Blend(p.object.blend_mode), target);
+            }
+        }
+    }
+
+    let colored = prepared
+        .iter()
+        .filter(|p| p.object.render_mode.colors_faces());
+    match target.transparency {
+        Transparency::Sorted => {
+            let mut triangles: Vec<(f32, &Prepared, usize)> = colored
+                .flat_map(|p| (0..p.object.triangles.len()).map(move |i| (p.depth(i), p, i)))
+                .collect();
+            triangles.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+            for (_, p, index) in triangles {
+                let output = FragmentOutput::BlendNoDepth(p.object.blend_mode);
+                p.fill(index, output, target);
+            }
+        }
+        Transparency::WeightedBlended => {
+            for p in colored {
+                for index in 0..p.object.triangles.len() {
+                    p.fill(index, FragmentOutput::WeightedBlended, target);
+                }
+            }
+            target.resolve_weighted();
+        }
+    }
+
+    for p in &prepared {
+        if p.object.render_mode.draws_edges() {
+            p.draw_edges(target);
+        }
+    }
+}
+
+// An object after the vertex stage, ready to be rasterized
+pub(super) struct Prepared<'a> {
+    pub(super) object: &'a Object,
+    pub(super) uniforms: Uniforms<'a>,
+    pub(super) outputs: Vec<VertexOutput>,
+    view_points: Vec<Point>,
+    pub(super) clip_points: Vec<Vec4>,
+}
+
+impl<'a> Prepared<'a> {
+    pub(super) fn new(
+        object: &'a Object,
+        cam: &super::space::Camera,
+        lights: &'a [super::light::Light],
+    ) -> Self {
+        let uniforms = Uniforms {
+            lights,
+            eye: cam.pos,
+            material: &object.material,
+            shading: object.shading,
+            texture: object.texture.as_deref(),
+        };
+
+        // Run the vertex stage over every point
+        let outputs: Vec<_> = object
+            .points
+            .iter()
+            .zip(object.vertex_normals())
+            .enumerate()
+            .map(|(index, (position, normal))| {
+                let vertex = Vertex {
+                    index,
+                    position: *position,
+                    normal,
+                    color: object.vertex_color(index),
+                };
+                object.shader.vertex(&vertex, &uniforms)
+            })
+            .collect();
+
+        let view = cam.view_matrix();
+        let projection = cam.projection_matrix();
+
+        // Move into camera space (+X right, +Y up, +Z forward), then clip space
+        let view_points: Vec<Point> = outputs.iter().map(|o| view * o.position).collect();
+        let clip_points: Vec<Vec4> = view_points
+            .iter()
+            .map(|p| projection * p.extend(1.0))
+            .collect();
+
+        Prepared {
+            object,
+            uniforms,
+            outputs,
+            view_points,
+            clip_points,
+        }
+    }
+
+    // View depth of the triangle's centroid
+    pub(super) fn depth(&self, index: usize) -> f32 {
+        let triangle = &self.object.triangles[index];
+        let points = &self.view_points;
+        (points[triangle.a].z + points[triangle.b].z + points[triangle.c].z) / 3.0
+    }
+
+    // Clips, culls and rasterizes one triangle
+    fn fill(&self, index: usize, output: FragmentOutput, target: &mut RenderTarget) {
+        let object = self.object;
+        let triangle = &object.triangles[index];
+        let outputs = &self.outputs;
+        let colors_faces = object.render_mode.colors_faces();
+
+        let corners = [
+            &outputs[triangle.a],
+            &outputs[triangle.b],
+            &outputs[triangle.c],
+        ];
+        let face_normal = face_normal(corners.map(|o| o.position));
+        let centroid = centroid(corners.map(|o| o.position));
+
+        let polygon = self.clip(index);
+        if polygon.len() < 3 {
+            return;
+        }
+
+        let mut screen: Vec<Vec4> = polygon
+            .iter()
+            .map(|v| to_screen(v.position, target.width, target.height))
+            .collect();
+
+        let Some(front_facing) = self.facing(&screen) else {
+            return;
+        };
+
+        // Like glPolygonOffset: edges take their depth from the line through
+        // the middle of their span, so the face is pushed back by as much as
+        // its depth changes over that distance. Edges lying on the face then
+        // win, while those behind it stay hidden at any distance.
+        if object.render_mode.draws_edges() {
+            let span = object.line_style.width.max(1.0) * target.antialiasing.scale() as f32;
+            let offset = depth_slope(&screen) * (span + 1.0) + MIN_DEPTH_OFFSET;
+            for corner in &mut screen {
+                corner.z += offset;
+            }
+        }
+
+        // Fan-triangulate what is left of the triangle after clipping
+        for i in 1..polygon.len() - 1 {
+            let varyings = [
+                &polygon[0].varyings,
+                &polygon[i].varyings,
+                &polygon[i + 1].varyings,
+            ];
+
+            fill_triangle(
+                target,
+                screen[0],
+                screen[i],
+                screen[i + 1],
+                output,
+                |x, y, weights| {
+                    if !colors_faces {
+                        return None;
+                    }
+                    let uv_at = |w: [f32; 3]| {
+                        varyings[0].vec2(UV_VARYINGS) * w[0]
+                            + varyings[1].vec2(UV_VARYINGS) * w[1]
+                            + varyings[2].vec2(UV_VARYINGS) * w[2]
+                    };
+                    let uv = uv_at(weights.center);
+                    let varyings = Varyings::interpolate3(varyings, weights.center);
+                    let distance = varyings.values[VIEW_DEPTH_VARYING];
+                    let fragment = Fragment {
+                        x,
+                        y,
+                        varyings,
+                        uv,
+                        uv_dx: uv_at(weights.right) - uv,
+                        uv_dy: uv_at(weights.below) - uv,
+                        face_normal,
+                        centroid,
+                        front_facing,
+                    };
+                    let color = object.shader.fragment(&fragment, &self.uniforms);
+                    Some((color, distance))
+                },
+            );
+        }
+    }
+
+    // The triangle with its UVs and view depth in the varyings, clipped to
+    // the view volume
+    pub(super) fn clip(&self, index: usize) -> Vec<ClipVertex> {
+        let triangle = &self.object.triangles[index];
+        let uvs = self.object.triangle_uvs(index);
+        let mut polygon = [triangle.a, triangle.b, triangle.c].map(|i| {
+            let mut varyings = self.outputs[i].varyings;
+            varyings.values[VIEW_DEPTH_VARYING] = self.view_points[i].z;
+            ClipVertex {
+                position: self.clip_points[i],
+                varyings,
+            }
+        });
+        for (corner, uv) in polygon.iter_mut().zip(uvs) {
+            corner.varyings.set_vec2(UV_VARYINGS, uv);
+        }
+        if polygon.iter().all(|v| clip::is_inside(v.position)) {
+            polygon.to_vec()
+        } else {
+            clip::clip_polygon(&polygon)
+        }
+    }
+
+    // Whether a clipped triangle with these screen corners faces the camera,
+    // or `None` if the cull mode skips it
+    pub(super) fn facing(&self, screen: &[Vec4]) -> Option<bool> {
+        // Positive area is counter-clockwise on screen, where y points down
+        let area = polygon_area(screen);
+        let front_facing = match self.object.front_face {
+            FrontFace::Clockwise => area < 0.0,
+            FrontFace::CounterClockwise => area > 0.0,
+        };
+        let culled = match self.object.cull_mode {
+            CullMode::None => false,
+            CullMode::Back => !front_facing,
+            CullMode::Front => front_facing,
+        };
+        (!culled).then_some(front_facing)
+    }
+
+    fn draw_edges(&self, target: &mut RenderTarget) {
+        let object = self.object;
+        let clip_points = &self.clip_points;
+
+        // Draw the points, blended like the lines through them
+        for (i, clip) in clip_points.iter().enumerate() {
+            if clip::is_inside(*clip) {
+                let screen = to_screen(*clip, target.width, target.height);
+                let (sx, sy) = (screen.x as usize, screen.y as usize);
+                if sx < target.width && sy < target.height {
+                    let idx = sy * target.width + sx;
+                    let color = object.vertex_color(i);
+                    target.composite(idx, screen.z, color, object.blend_mode, true);
+                }
+            }
+        }
+
+        // Draw wireframe lines for edges, clipped to the part inside the view
+        for edge in &object.edges {
+            let (a, b) = (clip_points[edge.start], clip_points[edge.end]);
+            let Some((t0, t1)) = clip::clip_line(a, b) else {
+                continue;
+            };
+
+            let (start_color, end_color) = match object.edge_color {
+                Some(color) => (color, color),
+                None => (
+                    object.vertex_color(edge.start),
+                    object.vertex_color(edge.end),
+                ),
+            };
+
+            let start = to_screen(a.lerp(b, t0), target.width, target.height);
+            let end = to_screen(a.lerp(b, t1), target.width, target.height);
+
+            draw_line(
+                target,
+                Point::new(start.x, start.y, start.z),
+                Point::new(end.x, end.y, end.z),
+                start_color.lerp(&end_color, t0),
+                start_color.lerp(&end_color, t1),
+                &object.line_style,
+                object.blend_mode,
+            );
+        }
+    }
+}
+
+// Perspective divide and viewport transform. Keeps `1 / w` in `w` for
+// perspective-correct interpolation.
+pub(super) fn to_screen(clip: Vec4, width: usize, height: usize) -> Vec4 {
+    let ndc = clip.project();
+    Vec4::new(
+        (ndc.x * 0.5 + 0.5) * width as f32,
+        (0.5 - ndc.y * 0.5) * height as f32,
+        ndc.z,
+        1.0 / clip.w,
+    )
+}
+
+/// Rasterizes a triangle whose corners are given as screen `x` and `y`, depth
+/// `z` and `w` holding the reciprocal of the clip-space w.
+///
+/// Depth is linear in screen space and every covered sample is depth tested
+/// against the target. Samples on an edge shared by two triangles are covered
+/// by only one of them. `shade` is called once per pixel with any visible
+/// sample. It receives the pixel coordinates and perspective-correct
+/// barycentric weights at the covered samples' centroid and returns the color
+/// and the view depth there, or `None` to only write depth. `output` says how
+/// colors reach the target.
+pub fn fill_triangle(
+    target: &mut RenderTarget,
+    a: Vec4,
+    b: Vec4,
+    c: Vec4,
+    output: FragmentOutput,
+    mut shade: impl FnMut(usize, usize, Barycentrics) -> Option<(Color, f32)>,
+) {
+    let area = edge_function(a, b, c.x, c.y);
+    if area == 0.0 {
+        return;
+    }
+
+    // Barycentric weights, normalised so winding does not matter
+    let weights = |x: f32, y: f32| {
+        [
+            edge_function(b, c, x, y) / area,
+            edge_function(c, a, x, y) / area,
+            edge_function(a, b, x, y) / area,
+        ]
+    };
+
+    // Top-left rule: samples exactly on an edge belong to the triangle only
+    // if the edge is a top or left one, so triangles sharing an edge never
+    // both cover its samples
+    let top_left = |from: Vec4, to: Vec4| {
+        // Direction of the edge with the inside on its left, as seen on screen
+        let (dx, dy) = (
+            (to.x - from.x) * area.signum(),
+            (to.y - from.y) * area.signum(),
+        );
+        dy > 0.0 || (dy == 0.0 && dx < 0.0)
+    };
+    let owned = [top_left(b, c), top_left(c, a), top_left(a, b)];
+
+    // Weights undoing the perspective distortion
+    let perspective = |x: f32, y: f32| {
+        let [w0, w1, w2] = weights(x, y);
+        let p = [w0 * a.w, w1 * b.w, w2 * c.w];
+        let sum = p[0] + p[1] + p[2];
+        p.map(|v| v / sum)
+    };
+
+    // Bounding box clamped to the screen
+    let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as usize;
+    let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as usize;
+    let max_x = (a.x.max(b.x).max(c.x).ceil().max(0.0) as usize).min(target.width);
+    let max_y = (a.y.max(b.y).max(c.y).ceil().max(0.0) as usize).min(target.height);
+
+    let positions = target.antialiasing.sample_positions();
+    let mut visible = Vec::with_capacity(positions.len());
+
+    for py in min_y..max_y {
+        for px in min_x..max_x {
+            let first_sample = (py * target.width + px) * target.samples;
+
+            // Samples inside the triangle and in front of what is there
+            visible.clear();
+            let (mut cx, mut cy) = (0.0, 0.0);
+            for (s, (ox, oy)) in positions.iter().enumerate() {
+                let (x, y) = (px as f32 + ox, py as f32 + oy);
+                let [w0, w1, w2] = weights(x, y);
+                let outside = [w0, w1, w2]
+                    .iter()
+                    .zip(owned)
+                    .any(|(&w, owned)| w < 0.0 || (w == 0.0 && !owned));
+                if outside {
+                    continue;
+                }
+
+                let z = w0 * a.z + w1 * b.z + w2 * c.z;
+                if z < target.depth[first_sample + s] {
+                    visible.push((first_sample + s, z));
+                    cx += x;
+                    cy += y;
+                }
+            }
+            if visible.is_empty() {
+                continue;
+            }
+
+            // Perspective-correct weights, shaded once for the whole pixel
+            let n = visible.len() as f32;
+            let (x, y) = (cx / n, cy / n);
+            let barycentrics = Barycentrics {
+                center: perspective(x, y),
+                right: perspective(x + 1.0, y),
+                below: perspective(x, y + 1.0),
+            };
+            let shaded = shade(px, py, barycentrics);
+
+            for &(idx, z) in &visible {
+                match (output, shaded) {
+                    (FragmentOutput::Blend(mode), Some((color, _))) => {
+                        target.color[idx] =
+                            mode.blend(color, target.color[idx], target.linear_blending);
+                        target.depth[idx] = z;
+                    }
+                    (FragmentOutput::BlendNoDepth(mode), Some((color, _))) => {
+                        target.color[idx] =
+                            mode.blend(color, target.color[idx], target.linear_blending);
+                    }
+                    (FragmentOutput::WeightedBlended, Some((color, distance))) => {
+                        target.accumulate(idx, color, distance);
+                    }
+                    (_, None) => target.depth[idx] = z,
+                }
+            }
+        }
+    }
+}
+
+/// Draws a line between two screen positions given as `(x, y, depth)`.
+///
+/// The line is walked along its major axis one pixel at a time, covering a
+/// span across the minor axis that gives it `style.width` pixels of
+/// perpendicular thickness, `style.width * sqrt(1 + slope^2)` pixels long.
+/// With antialiasing each pixel's alpha is how much of it the span covers, so
+/// its borders fade out; without it a pixel is either in or out.
+pub fn draw_line(
+    target: &mut RenderTarget,
+    start: Point,
+    end: Point,
+    start_color: Color,
+    end_color: Color,
+    style: &LineStyle,
+    mode: BlendMode,
+) {
+    let dx = end.x - start.x;
+    let dy = end.y - start.y;
+
+    // Walk along u, the major axis, and spread the width across v
+    let steep = dy.abs() > dx.abs();
+    let (u0, v0, u1, v1) = if steep {
+        (start.y, start.x, end.y, end.x)
+    } else {
+        (start.x, start.y, end.x, end.y)
+    };
+    let du = u1 - u0;
+
+    if du.abs() < f32::EPSILON {
+        // Single pixel
+        let (px, py) = (start.x.floor(), start.y.floor());
+        if px >= 0.0 && py >= 0.0 && (px as usize) < target.width && (py as usize) < target.height {
+            let idx = py as usize * target.width + px as usize;
+            target.composite(idx, start.z, start_color, mode, true);
+        }
+        return;
+    }
+
+    // Widths and dashes are in output pixels, which may be several internal ones
+    let scale = target.antialiasing.scale() as f32;
+    let slope = (v1 - v0) / du;
+    let stretch = (1.0 + slope * slope).sqrt();
+    let half_span = style.width.max(1.0) * scale * stretch / 2.0;
+
+    // Endpoints sit on pixel centers, the line reaching half a pixel past them
+    let (u_min, u_max) = (u0.min(u1) - 0.5, u0.max(u1) + 0.5);
+    let (limit_u, limit_v) = if steep {
+        (target.height, target.width)
+    } else {
+        (target.width, target.height)
+    };
+
+    let positions = target.antialiasing.sample_positions();
+
+    let first = u_min.floor().max(0.0) as usize;
+    let last = (u_max.ceil().max(0.0) as usize).min(limit_u);
+
+    for u in first..last {
+        let center_u = u as f32 + 0.5;
+        let t = ((center_u - u0) / du).clamp(0.0, 1.0);
+
+        // Distance travelled from the start, for the dash pattern
+        if !style.is_on((center_u - u0).abs() * stretch / scale) {
+            continue;
+        }
+
+        let z = start.z + (end.z - start.z) * t;
+        let color = start_color.lerp(&end_color, t);
+
+        // Partial coverage of the end columns
+        let end_coverage = overlap(u as f32, u_min, u_max);
+
+        let center_v = v0 + slope * (center_u - u0);
+        let (lo, hi) = (center_v - half_span, center_v + half_span);
+        let first_v = lo.floor().max(0.0) as usize;
+        let last_v = (hi.ceil().max(0.0) as usize).min(limit_v);
+
+        for v in first_v..last_v {
+            let (px, py) = if steep { (v, u) } else { (u, v) };
+            let idx = py * target.width + px;
+
+            if !style.antialiased {
+                // In or out per sample, so multisampling still smooths the edges
+                for (s, (ox, oy)) in positions.iter().enumerate() {
+                    let (su, sv) = if steep { (*oy, *ox) } else { (*ox, *oy) };
+                    let sample_v = v0 + slope * (u as f32 + su - u0);
+                    if (sample_v - half_span..sample_v + half_span).contains(&(v as f32 + sv)) {
+                        target.composite_sample(idx * target.samples + s, z, color, mode, true);
+                    }
+                }
+                continue;
+            }
+
+            let coverage = overlap(v as f32, lo, hi) * end_coverage;
+            if coverage <= 0.0 {
+                continue;
+            }
+            let alpha = (color.a as f32 * coverage).round() as u8;
+            target.composite(idx, z, Color { a: alpha, ..color }, mode, coverage >= 0.5);
+        }
+    }
+}
+
+// Length of the overlap between the pixel [start, start + 1) and [lo, hi]
+fn overlap(start: f32, lo: f32, hi: f32) -> f32 {
+    ((start + 1.0).min(hi) - start.max(lo)).clamp(0.0, 1.0)
+}
+
+/// Composites `rgba` over a `background` pixel of the display buffer, which
+/// is treated as opaque, and packs the result in the same `format`.
+pub fn blend(background: u32, rgba: Color, format: PixelFormat) -> u32 {
+    let background = Color {
+        a: 255,
+        ..format.unpack(background)
+    };
+    format.pack(rgba.over(&background))
+}
+
+// Twice the signed area of a screen-space polygon (shoelace formula)
+pub(super) fn polygon_area(points: &[Vec4]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    -area
+}
+
+// Twice the signed area of the triangle (a, b, p). The ends are taken in a
+// fixed order, so triangles on both sides of an edge get exactly opposite values.
+fn edge_function(a: Vec4, b: Vec4, x: f32, y: f32) -> f32 {
+    if (a.x, a.y) > (b.x, b.y) {
+        return -edge_function(b, a, x, y);
+    }
+    (x - a.x) * (b.y - a.y) - (y - a.y) * (b.x - a.x)
+}
+
+// Unit normal of the triangle (a, b, c), counter-clockwise winding facing it
+pub(super) fn face_normal(corners: [Point; 3]) -> Point {
+    let [a, b, c] = corners;
+    (b - a).cross(c - a).normalize()
+}
+
+// Largest change of depth per pixel across the plane of a screen-space polygon
+fn depth_slope(points: &[Vec4]) -> f32 {
+    let [a, b, c] = [points[0], points[1], points[2]];
+    let (u, v) = (b - a, c - a);
+    let area = u.x * v.y - v.x * u.y;
+    if area == 0.0 {
+        return 0.0;
+    }
+    let dz_dx = (u.z * v.y - v.z * u.y) / area;
+    let dz_dy = (v.z * u.x - u.z * v.x) / area;
+    dz_dx.abs().max(dz_dy.abs())
+}
+
+// Average of the corners of the triangle (a, b, c)
+pub(super) fn centroid(corners: [Point; 3]) -> Point {
+    let [a, b, c] = corners;
+    (a + b + c) / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(style: &LineStyle, start: (f32, f32), end: (f32, f32)) -> RenderTarget {
+        let mut target = RenderTarget::new(32, 32, AntiAliasing::None);
+        let (start, end) = (
+            Point::new(start.0, start.1, 0.5),
+            Point::new(end.0, end.1, 0.5),
+        );
+        draw_line(
+            &mut target,
+            start,
+            end,
+            Color::WHITE,
+            Color::WHITE,
+            style,
+            BlendMode::Alpha,
+        );
+        target
+    }
+
+    fn alpha(target: &RenderTarget, x: usize, y: usize) -> u8 {
+        target.color[y * target.width + x].a
+    }
+
+    fn lit_in_column(target: &RenderTarget, x: usize) -> usize {
+        (0..target.height)
+            .filter(|&y| alpha(target, x, y) > 0)
+            .count()
+    }
+
+    fn lit_in_row(target: &RenderTarget, y: usize) -> usize {
+        (0..target.width)
+            .filter(|&x| alpha(target, x, y) > 0)
+            .count()
+    }
+
+    #[test]
+    fn axis_aligned_lines_are_width_pixels_thick() {
+        for width in [1.0, 3.0] {
+            let style = LineStyle::default().with_width(width);
+            let across = draw(&style, (4.5, 16.5), (27.5, 16.5));
+            let down = draw(&style, (16.5, 4.5), (16.5, 27.5));
+            for i in 4..28 {
+                assert_eq!(lit_in_column(&across, i), width as usize, "width {width}");
+                assert_eq!(lit_in_row(&down, i), width as usize, "width {width}");
+            }
+            assert_eq!(lit_in_column(&across, 3), 0);
+            assert_eq!(lit_in_column(&across, 28), 0);
+        }
+    }
+
+    #[test]
+    fn diagonal_lines_span_width_times_sqrt_2_per_column() {
+        for width in [1.0f32, 3.0] {
+            let style = LineStyle::default().with_width(width);
+            let target = draw(&style, (4.5, 4.5), (27.5, 27.5));
+            let span = width * 2.0f32.sqrt();
+            for x in 8..24 {
+                let lit = lit_in_column(&target, x) as f32;
+                assert!(
+                    lit == span.floor() || lit == span.ceil(),
+                    "width {width}: {lit}"
+                );
+                // Centered on the diagonal
+                assert!(alpha(&target, x, x) > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn dashes_leave_gaps_along_the_line() {
+        let target = draw(&LineStyle::dashed(4.0, 4.0), (0.5, 8.5), (31.5, 8.5));
+        for x in 0..32 {
+            assert_eq!(alpha(&target, x, 8) > 0, (x / 4) % 2 == 0, "x {x}");
+        }
+
+        let dotted = draw(&LineStyle::dotted(2.0), (0.5, 8.5), (31.5, 8.5));
+        assert_eq!(lit_in_row(&dotted, 8), 11);
+    }
+
+    #[test]
+    fn antialiased_lines_partially_cover_their_borders() {
+        let style = LineStyle::default().with_antialiasing(true);
+
+        // Centered on a pixel row, the line covers just that row
+        let centered = draw(&style, (4.5, 16.5), (27.5, 16.5));
+        assert_eq!(alpha(&centered, 16, 16), 255);
+        assert_eq!(alpha(&centered, 16, 15), 0);
+        assert_eq!(alpha(&centered, 16, 17), 0);
+
+        // Between two rows, it covers half of each
+        let between = draw(&style, (4.5, 16.0), (27.5, 16.0));
+        for y in [15, 16] {
+            assert!((120..=136).contains(&alpha(&between, 16, y)), "row {y}");
+        }
+
+        // A quarter of the way, three quarters of one and a quarter of the other
+        let quarter = draw(&style, (4.5, 16.25), (27.5, 16.25));
+        assert!((56..=72).contains(&alpha(&quarter, 16, 15)));
+        assert!((184..=200).contains(&alpha(&quarter, 16, 16)));
+
+        // A thick diagonal line is solid inside and fades at its borders
+        let diagonal = draw(&style.with_width(3.0), (4.5, 4.5), (27.5, 27.5));
+        let column: Vec<u8> = (10..23).map(|y| alpha(&diagonal, 16, y)).collect();
+        assert_eq!(alpha(&diagonal, 16, 16), 255);
+        assert!(column.iter().any(|&a| a > 0 && a < 255), "{column:?}");
+    }
+}
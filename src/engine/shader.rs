@@ -1,4 +1,9 @@
-#[derive(Debug, Clone, Copy)]
+use super::light::{Light, Lighting, illuminate};
+use super::math::Vec2;
+use super::object::Point;
+use super::texture::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -6,189 +11,593 @@ pub struct Color {
     pub a: u8,
 }
 
-pub struct Shader {}
+impl Color {
+    pub const TRANSPARENT: Color = Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 0,
+    };
 
-impl Shader {
-    pub fn new() -> Self {
-        Shader {}
-    }
-
-    pub fn render(
-        object: &super::object::Object,
-        color: &Color,
-        cam: &super::space::Camera,
-    ) -> Vec<Color> {
-        let mut buffer = vec![
-            Color {
-                r: 0,
-                g: 0,
-                b: 0,
-                a: 0
-            };
-            cam.width * cam.height
-        ];
+    pub const WHITE: Color = Color {
+        r: 255,
+        g: 255,
+        b: 255,
+        a: 255,
+    };
 
-        // Create depth buffer for Z-sorting
-        let mut depth_buffer = vec![f32::INFINITY; cam.width * cam.height];
+    /// RGB channels as `0.0..=1.0` factors multiplied by `factor`.
+    pub fn scaled_rgb(&self, factor: f32) -> [f32; 3] {
+        [
+            self.r as f32 / 255.0 * factor,
+            self.g as f32 / 255.0 * factor,
+            self.b as f32 / 255.0 * factor,
+        ]
+    }
+
+    /// Composites this color over `background` using straight alpha.
+    pub fn over(&self, background: &Color) -> Color {
+        let sa = self.a as f32 / 255.0;
+        let da = background.a as f32 / 255.0;
+        let out_a = sa + da * (1.0 - sa);
+        if out_a <= 0.0 {
+            return Color::TRANSPARENT;
+        }
 
-        // Convert FOV from degrees to radians
-        let fov_rad = cam.fov * std::f32::consts::PI / 180.0;
-        let aspect_ratio = cam.width as f32 / cam.height as f32;
-        let tan_half_fov = (fov_rad / 2.0).tan();
+        let channel = |s: u8, d: u8| {
+            let v = (s as f32 * sa + d as f32 * da * (1.0 - sa)) / out_a;
+            v.round().clamp(0.0, 255.0) as u8
+        };
+        Color {
+            r: channel(self.r, background.r),
+            g: channel(self.g, background.g),
+            b: channel(self.b, background.b),
+            a: (out_a * 255.0).round() as u8,
+        }
+    }
 
-        // Project all points to screen space and track which are visible
-        let mut screen_points = Vec::with_capacity(object.points.len());
-        let mut point_visible = vec![false; object.points.len()];
+    /// Linear interpolation towards `other`, with `t` in `0.0..=1.0`.
+    pub fn lerp(&self, other: &Color, t: f32) -> Color {
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+            a: channel(self.a, other.a),
+        }
+    }
 
-        for (i, point) in object.points.iter().enumerate() {
-            let rel_x = point.x - cam.pos.x;
-            let rel_y = point.y - cam.pos.y;
-            let rel_z = point.z - cam.pos.z;
+    /// Multiplies each channel, including alpha, by the same channel of `other`.
+    pub fn modulate(&self, other: &Color) -> Color {
+        let channel = |a: u8, b: u8| ((a as u32 * b as u32 + 127) / 255) as u8;
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+            a: channel(self.a, other.a),
+        }
+    }
 
-            // Early culling
-            if rel_z <= 1.0 {
-                screen_points.push((0, 0, 0.0)); // Dummy value
-                continue;
+    /// Modulates the color by incoming light given as RGB factors, keeping alpha.
+    pub fn lit(&self, light: [f32; 3]) -> Color {
+        let channel = |c: u8, l: f32| (c as f32 * l).round().clamp(0.0, 255.0) as u8;
+        Color {
+            r: channel(self.r, light[0]),
+            g: channel(self.g, light[1]),
+            b: channel(self.b, light[2]),
+            a: self.a,
+        }
+    }
+}
+
+/// How a fragment is combined with the color already in the render target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Straight-alpha "over".
+    #[default]
+    Alpha,
+    /// "Over" for colors whose RGB is already multiplied by their alpha.
+    Premultiplied,
+    /// Adds the color weighted by its alpha, for glows and particles.
+    Additive,
+    /// Darkens by multiplying the channels.
+    Multiply,
+    /// Lightens by multiplying the inverted channels.
+    Screen,
+    /// Keeps the darker of each channel.
+    Min,
+    /// Keeps the lighter of each channel.
+    Max,
+}
+
+impl BlendMode {
+    /// Combines `source` with `destination`, both with straight alpha. With
+    /// `linear` the RGB channels are blended in linear light rather than sRGB.
+    pub fn blend(self, source: Color, destination: Color, linear: bool) -> Color {
+        if self == BlendMode::Alpha && source.a == 255 {
+            return source;
+        }
+
+        let decode = |c: u8| {
+            let v = c as f32 / 255.0;
+            if linear { srgb_to_linear(v) } else { v }
+        };
+        let sa = source.a as f32 / 255.0;
+        let da = destination.a as f32 / 255.0;
+
+        // Premultiplied channels are sRGB encoded after the multiplication, so
+        // they have to be divided by alpha again before decoding. Without alpha
+        // they are light added on top and decode as is.
+        let decode_source = |c: u8| {
+            if self == BlendMode::Premultiplied && linear && sa > 0.0 {
+                srgb_to_linear(c as f32 / 255.0 / sa) * sa
+            } else {
+                decode(c)
             }
+        };
+        let s = [
+            decode_source(source.r),
+            decode_source(source.g),
+            decode_source(source.b),
+        ];
+        let d = [
+            decode(destination.r),
+            decode(destination.g),
+            decode(destination.b),
+        ];
 
-            // Frustum culling
-            let half_width = rel_z * tan_half_fov * aspect_ratio;
-            let half_height = rel_z * tan_half_fov;
+        // Premultiplied result of each channel, following the W3C compositing
+        // model: the blend function applies where both are present
+        let separable = |f: fn(f32, f32) -> f32| {
+            let mut out = [0.0; 3];
+            for i in 0..3 {
+                let mixed = (1.0 - da) * s[i] + da * f(s[i], d[i]);
+                out[i] = sa * mixed + da * d[i] * (1.0 - sa);
+            }
+            out
+        };
 
-            if rel_x.abs() > half_width || rel_y.abs() > half_height {
-                screen_points.push((0, 0, 0.0)); // Dummy value
-                continue;
+        let over_alpha = sa + da * (1.0 - sa);
+        let (rgb, alpha) = match self {
+            BlendMode::Alpha => (separable(|s, _| s), over_alpha),
+            BlendMode::Premultiplied => {
+                let out = [0, 1, 2].map(|i| s[i] + da * d[i] * (1.0 - sa));
+                (out, over_alpha)
             }
+            BlendMode::Additive => {
+                let out = [0, 1, 2].map(|i| sa * s[i] + da * d[i]);
+                (out, (sa + da).min(1.0))
+            }
+            BlendMode::Multiply => (separable(|s, d| s * d), over_alpha),
+            BlendMode::Screen => (separable(|s, d| s + d - s * d), over_alpha),
+            BlendMode::Min => (separable(f32::min), over_alpha),
+            BlendMode::Max => (separable(f32::max), over_alpha),
+        };
+        if alpha <= 0.0 {
+            return Color::TRANSPARENT;
+        }
+
+        let encode = |v: f32| {
+            let v = (v / alpha).clamp(0.0, 1.0);
+            let v = if linear { linear_to_srgb(v) } else { v };
+            (v * 255.0).round() as u8
+        };
+        Color {
+            r: encode(rgb[0]),
+            g: encode(rgb[1]),
+            b: encode(rgb[2]),
+            a: (alpha * 255.0).round() as u8,
+        }
+    }
+}
+
+/// Decodes an sRGB channel in `0.0..=1.0` to linear light.
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear light channel in `0.0..=1.0` as sRGB.
+pub fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
 
-            // Project to screen space
-            let screen_x =
-                ((rel_x / rel_z / (tan_half_fov * aspect_ratio)) * 0.5 + 0.5) * cam.width as f32;
-            let screen_y = ((rel_y / rel_z / tan_half_fov) * 0.5 + 0.5) * cam.height as f32;
+/// How `StandardShader` lights filled triangles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadingMode {
+    /// The object color as is, ignoring lights.
+    #[default]
+    Unlit,
+    /// Lighting evaluated once per triangle, at its centroid with its face normal.
+    Flat,
+    /// Lighting evaluated at each point from vertex normals and interpolated.
+    Gouraud,
+    /// Blinn-Phong lighting evaluated per pixel from interpolated normals.
+    Phong,
+}
 
-            let sx = screen_x as usize;
-            let sy = screen_y as usize;
+/// Surface material of an object.
+#[derive(Debug, Clone, Copy)]
+pub struct Material {
+    /// Reflectance for ambient light.
+    pub ambient: Color,
+    /// Reflectance for direct light.
+    pub diffuse: Color,
+    /// Color of specular highlights.
+    pub specular: Color,
+    /// Blinn-Phong exponent; higher values give smaller, sharper highlights.
+    pub shininess: f32,
+}
 
-            if sx < cam.width && sy < cam.height {
-                point_visible[i] = true;
-                screen_points.push((sx, sy, rel_z));
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            ambient: Color::WHITE,
+            diffuse: Color::WHITE,
+            specular: Color::WHITE,
+            shininess: 32.0,
+        }
+    }
+}
 
-                // Draw the point
-                let idx = sy * cam.width + sx;
-                if idx < buffer.len() && rel_z < depth_buffer[idx] {
-                    buffer[idx] = *color;
-                    depth_buffer[idx] = rel_z;
-                }
-            } else {
-                screen_points.push((0, 0, 0.0)); // Dummy value
+impl Material {
+    pub fn new() -> Self {
+        Material::default()
+    }
+
+    pub fn with_colors(ambient: Color, diffuse: Color, specular: Color, shininess: f32) -> Self {
+        Material {
+            ambient,
+            diffuse,
+            specular,
+            shininess,
+        }
+    }
+
+    /// Combines incoming light with the material and the object's base color.
+    pub fn shade(&self, base: &Color, lighting: &Lighting) -> Color {
+        let ambient = self.ambient.scaled_rgb(1.0);
+        let diffuse = self.diffuse.scaled_rgb(1.0);
+        let specular = self.specular.scaled_rgb(1.0);
+
+        let mut albedo = [0.0; 3];
+        let mut highlight = [0.0; 3];
+        for i in 0..3 {
+            albedo[i] = ambient[i] * lighting.ambient[i] + diffuse[i] * lighting.diffuse[i];
+            highlight[i] = specular[i] * lighting.specular[i] * 255.0;
+        }
+
+        let lit = base.lit(albedo);
+        let channel = |c: u8, h: f32| (c as f32 + h).round().clamp(0.0, 255.0) as u8;
+        Color {
+            r: channel(lit.r, highlight[0]),
+            g: channel(lit.g, highlight[1]),
+            b: channel(lit.b, highlight[2]),
+            a: lit.a,
+        }
+    }
+}
+
+/// Number of `f32` slots available for values passed from the vertex to the fragment stage.
+pub const MAX_VARYINGS: usize = 16;
+
+/// Offset of the two varyings the rasterizer fills with the texture
+/// coordinates of each triangle corner, overwriting what the vertex stage put there.
+pub const UV_VARYINGS: usize = MAX_VARYINGS - 2;
+
+/// Offset of the varying the rasterizer fills with the view-space depth of
+/// each triangle corner, overwriting what the vertex stage put there.
+pub const VIEW_DEPTH_VARYING: usize = UV_VARYINGS - 1;
+
+/// Values emitted per vertex and interpolated across each primitive.
+#[derive(Debug, Clone, Copy)]
+pub struct Varyings {
+    pub values: [f32; MAX_VARYINGS],
+}
+
+impl Default for Varyings {
+    fn default() -> Self {
+        Varyings {
+            values: [0.0; MAX_VARYINGS],
+        }
+    }
+}
+
+impl Varyings {
+    /// Weighted sum of three sets of varyings.
+    pub fn interpolate3(varyings: [&Varyings; 3], weights: [f32; 3]) -> Varyings {
+        let mut out = Varyings::default();
+        for (v, w) in varyings.iter().zip(weights) {
+            for (o, value) in out.values.iter_mut().zip(v.values) {
+                *o += value * w;
             }
         }
+        out
+    }
 
-        // Draw wireframe lines for edges
-        let line_color = Color {
-            r: 200,
-            g: 200,
-            b: 200,
-            a: 255,
-        };
-        for edge in &object.edges {
-            if point_visible[edge.start] && point_visible[edge.end] {
-                let (start_x, start_y, start_z) = screen_points[edge.start];
-                let (end_x, end_y, end_z) = screen_points[edge.end];
-
-                Self::draw_line(
-                    &mut buffer,
-                    &mut depth_buffer,
-                    cam.width,
-                    cam.height,
-                    start_x,
-                    start_y,
-                    start_z,
-                    end_x,
-                    end_y,
-                    end_z,
-                    line_color,
-                );
+    /// Linear interpolation towards `other`, with `t` in `0.0..=1.0`.
+    pub fn lerp(&self, other: &Varyings, t: f32) -> Varyings {
+        let mut out = *self;
+        for (o, value) in out.values.iter_mut().zip(other.values) {
+            *o += (value - *o) * t;
+        }
+        out
+    }
+
+    pub fn set_point(&mut self, offset: usize, point: Point) {
+        self.values[offset..offset + 3].copy_from_slice(&[point.x, point.y, point.z]);
+    }
+
+    pub fn point(&self, offset: usize) -> Point {
+        Point {
+            x: self.values[offset],
+            y: self.values[offset + 1],
+            z: self.values[offset + 2],
+        }
+    }
+
+    /// Stores a color as four `0.0..=255.0` channels.
+    pub fn set_color(&mut self, offset: usize, color: Color) {
+        self.values[offset..offset + 4].copy_from_slice(&[
+            color.r as f32,
+            color.g as f32,
+            color.b as f32,
+            color.a as f32,
+        ]);
+    }
+
+    pub fn color(&self, offset: usize) -> Color {
+        let channel = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+        Color {
+            r: channel(self.values[offset]),
+            g: channel(self.values[offset + 1]),
+            b: channel(self.values[offset + 2]),
+            a: channel(self.values[offset + 3]),
+        }
+    }
+
+    pub fn set_vec2(&mut self, offset: usize, v: Vec2) {
+        self.values[offset..offset + 2].copy_from_slice(&[v.x, v.y]);
+    }
+
+    pub fn vec2(&self, offset: usize) -> Vec2 {
+        Vec2::new(self.values[offset], self.values[offset + 1])
+    }
+}
+
+/// Per-point input of the vertex stage, in world space.
+#[derive(Debug, Clone, Copy)]
+pub struct Vertex {
+    pub index: usize,
+    pub position: Point,
+    pub normal: Point,
+    pub color: Color,
+}
+
+/// Result of the vertex stage.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexOutput {
+    /// World-space position handed on to projection.
+    pub position: Point,
+    pub varyings: Varyings,
+}
+
+/// Input of the fragment stage for one covered pixel.
+#[derive(Debug, Clone, Copy)]
+pub struct Fragment {
+    pub x: usize,
+    pub y: usize,
+    pub varyings: Varyings,
+    /// Perspective-correct texture coordinates.
+    pub uv: Vec2,
+    /// Change of `uv` to the next pixel to the right.
+    pub uv_dx: Vec2,
+    /// Change of `uv` to the next pixel down.
+    pub uv_dy: Vec2,
+    /// Unit normal of the triangle being filled, from the vertex stage positions.
+    pub face_normal: Point,
+    /// Center of the triangle being filled, from the vertex stage positions.
+    pub centroid: Point,
+    /// Whether the triangle faces the camera according to the object's `FrontFace`.
+    pub front_facing: bool,
+}
+
+/// Per-object state shared by every invocation of a shader during a draw.
+pub struct Uniforms<'a> {
+    pub lights: &'a [Light],
+    /// Camera position.
+    pub eye: Point,
+    pub material: &'a Material,
+    pub shading: ShadingMode,
+    pub texture: Option<&'a Texture>,
+}
+
+/// Programmable stages run for every object drawn with filled triangles.
+pub trait Shader {
+    /// Transforms a point and emits the varyings interpolated for its fragments.
+    fn vertex(&self, vertex: &Vertex, uniforms: &Uniforms) -> VertexOutput;
+
+    /// Computes the color of a covered pixel.
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color;
+}
+
+/// The built-in shader: lights the object's material according to its `ShadingMode`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StandardShader;
+
+impl StandardShader {
+    const POSITION: usize = 0;
+    const NORMAL: usize = 3;
+    const COLOR: usize = 6;
+
+    fn shade_at(base: &Color, position: Point, normal: Point, uniforms: &Uniforms) -> Color {
+        let material = uniforms.material;
+        let lighting = illuminate(
+            uniforms.lights,
+            position,
+            normal,
+            uniforms.eye,
+            material.shininess,
+        );
+        material.shade(base, &lighting)
+    }
+}
+
+impl Shader for StandardShader {
+    fn vertex(&self, vertex: &Vertex, uniforms: &Uniforms) -> VertexOutput {
+        let mut varyings = Varyings::default();
+        varyings.set_point(Self::POSITION, vertex.position);
+        varyings.set_point(Self::NORMAL, vertex.normal);
+
+        let color = match uniforms.shading {
+            ShadingMode::Gouraud => {
+                Self::shade_at(&vertex.color, vertex.position, vertex.normal, uniforms)
             }
+            _ => vertex.color,
+        };
+        varyings.set_color(Self::COLOR, color);
+
+        VertexOutput {
+            position: vertex.position,
+            varyings,
+        }
+    }
+
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let varyings = &fragment.varyings;
+        let mut color = varyings.color(Self::COLOR);
+        if let Some(texture) = uniforms.texture {
+            let lod = texture.level_of_detail(fragment.uv_dx, fragment.uv_dy);
+            color = color.modulate(&texture.sample(fragment.uv, lod));
         }
 
-        buffer
-    }
-
-    pub fn draw_line(
-        buffer: &mut [Color],
-        depth_buffer: &mut [f32],
-        width: usize,
-        height: usize,
-        start_x: usize,
-        start_y: usize,
-        start_z: f32,
-        end_x: usize,
-        end_y: usize,
-        end_z: f32,
-        color: Color,
-    ) {
-        // Bresenham's line algorithm
-        let dx = end_x as isize - start_x as isize;
-        let dy = end_y as isize - start_y as isize;
-
-        let abs_dx = dx.abs();
-        let abs_dy = dy.abs();
-
-        let mut x = start_x as isize;
-        let mut y = start_y as isize;
-
-        // Calculate the number of steps to take
-        let steps = if abs_dx > abs_dy { abs_dx } else { abs_dy };
-
-        if steps == 0 {
-            // Single pixel
-            let idx = (y as usize) * width + (x as usize);
-            if idx < buffer.len() {
-                let z = start_z;
-                if z < depth_buffer[idx] {
-                    buffer[idx] = color;
-                    depth_buffer[idx] = z;
-                }
+        match uniforms.shading {
+            ShadingMode::Unlit | ShadingMode::Gouraud => color,
+            // The same position and normal for every pixel, so point and spot
+            // lights do not vary across the face
+            ShadingMode::Flat => {
+                Self::shade_at(&color, fragment.centroid, fragment.face_normal, uniforms)
+            }
+            ShadingMode::Phong => {
+                let position = varyings.point(Self::POSITION);
+                let normal = varyings.point(Self::NORMAL).normalize();
+                Self::shade_at(&color, position, normal, uniforms)
             }
-            return;
         }
+    }
+}
 
-        let x_inc = dx as f32 / steps as f32;
-        let y_inc = dy as f32 / steps as f32;
-        let z_inc = (end_z - start_z) / steps as f32;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut x_f = start_x as f32;
-        let mut y_f = start_y as f32;
-        let mut z = start_z;
+    fn color(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
 
-        for _ in 0..=steps {
-            let px = x_f as usize;
-            let py = y_f as usize;
+    fn assert_close(a: Color, b: Color) {
+        let channels = |c: Color| [c.r, c.g, c.b, c.a];
+        for (x, y) in channels(a).into_iter().zip(channels(b)) {
+            assert!(x.abs_diff(y) <= 1, "{a:?} != {b:?}");
+        }
+    }
 
-            if px < width && py < height {
-                let idx = py * width + px;
-                if idx < buffer.len() && z < depth_buffer[idx] {
-                    buffer[idx] = color;
-                    depth_buffer[idx] = z;
-                }
-            }
+    #[test]
+    fn premultiplied_matches_straight_alpha() {
+        let background = color(10, 120, 230, 255);
+        let straight = color(200, 100, 50, 128);
+        let premultiplied = color(100, 50, 25, 128);
+        for linear in [false, true] {
+            assert_close(
+                BlendMode::Premultiplied.blend(premultiplied, background, linear),
+                BlendMode::Alpha.blend(straight, background, linear),
+            );
+        }
+    }
+
+    #[test]
+    fn linear_blending_brightens_mixes() {
+        let black = color(0, 0, 0, 255);
+        let white = color(255, 255, 255, 128);
+        let srgb = BlendMode::Alpha.blend(white, black, false);
+        let linear = BlendMode::Alpha.blend(white, black, true);
+        assert_close(srgb, color(128, 128, 128, 255));
+        // Half the light is about 188 once encoded as sRGB
+        assert_close(linear, color(188, 188, 188, 255));
+    }
 
-            x_f += x_inc;
-            y_f += y_inc;
-            z += z_inc;
+    #[test]
+    fn srgb_conversions_invert_each_other() {
+        for i in 0..=255 {
+            let v = i as f32 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(v)) - v).abs() < 1e-5);
         }
     }
 
-    pub fn blend(background: u32, rgba: Color) -> u32 {
-        let r = rgba.r as u32;
-        let g = rgba.g as u32;
-        let b = rgba.b as u32;
-        let a = rgba.a as u32;
+    #[test]
+    fn material_scales_light_per_term() {
+        let material = Material::with_colors(
+            color(255, 0, 0, 255),
+            color(0, 255, 0, 255),
+            color(0, 0, 255, 255),
+            8.0,
+        );
+        let lighting = Lighting {
+            ambient: [0.5; 3],
+            diffuse: [0.25; 3],
+            specular: [0.1; 3],
+        };
+        // Ambient and diffuse tint the base color, specular adds on top
+        let shaded = material.shade(&color(200, 200, 200, 128), &lighting);
+        assert_close(shaded, color(100, 50, 26, 128));
+    }
+
+    #[test]
+    fn gouraud_and_phong_agree_at_vertices() {
+        let lights = [
+            Light::ambient(Color::WHITE, 0.1),
+            Light::point(Point::new(30.0, 40.0, -20.0), Color::WHITE, 1.0),
+        ];
+        let material = Material::default();
+        let vertex = Vertex {
+            index: 0,
+            position: Point::new(5.0, 3.0, 0.0),
+            normal: Point::new(0.3, 0.4, -1.0).normalize(),
+            color: color(180, 120, 60, 255),
+        };
 
-        let r = (r * a + (255 - a) * (background & 0xFF)) / 255;
-        let g = (g * a + (255 - a) * ((background >> 8) & 0xFF)) / 255;
-        let b = (b * a + (255 - a) * ((background >> 16) & 0xFF)) / 255;
+        let shade = |shading: ShadingMode| {
+            let uniforms = Uniforms {
+                lights: &lights,
+                eye: Point::new(0.0, 0.0, -50.0),
+                material: &material,
+                shading,
+                texture: None,
+            };
+            let output = StandardShader.vertex(&vertex, &uniforms);
+            let fragment = Fragment {
+                x: 0,
+                y: 0,
+                varyings: output.varyings,
+                uv: Vec2::default(),
+                uv_dx: Vec2::default(),
+                uv_dy: Vec2::default(),
+                face_normal: vertex.normal,
+                centroid: vertex.position,
+                front_facing: true,
+            };
+            StandardShader.fragment(&fragment, &uniforms)
+        };
 
-        (r | (g << 8) | (b << 16)) as u32
+        let gouraud = shade(ShadingMode::Gouraud);
+        assert_ne!(gouraud, vertex.color);
+        assert_close(shade(ShadingMode::Phong), gouraud);
     }
 }
@@ -1,8 +1,33 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+use super::math::Quaternion;
+use super::object::{Matrix4x4, Point};
+
+/// How camera space is mapped onto the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Symmetric perspective with a vertical field of view in degrees.
+    Perspective { fov: f32 },
+    /// Parallel projection showing `height` world units vertically.
+    Orthographic { height: f32 },
+    /// Off-axis perspective given by the edges of the view on the near plane.
+    Frustum {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+}
 
 pub struct Camera {
-    pub pos: super::object::Point,
-    pub fov: f32,
+    pub pos: Point,
+    /// Rotation from camera space (+X right, +Y up, +Z forward) to world space.
+    pub orientation: Quaternion,
+    pub projection: Projection,
+    /// Distance to the near clipping plane.
+    pub near: f32,
+    /// Distance to the far clipping plane.
+    pub far: f32,
     pub width: usize,
     pub height: usize,
 }
@@ -15,29 +40,191 @@ impl Camera {
                 y: 0.0,
                 z: -50.0,
             },
-            fov: 60.0,
+            orientation: Quaternion::identity(),
+            projection: Projection::Perspective { fov: 60.0 },
+            near: 1.0,
+            far: 10000.0,
             width,
             height,
         }
     }
+
+    pub fn forward(&self) -> Point {
+        self.orientation.rotate_point(&Point::new(0.0, 0.0, 1.0))
+    }
+
+    pub fn right(&self) -> Point {
+        self.orientation.rotate_point(&Point::new(1.0, 0.0, 0.0))
+    }
+
+    pub fn up(&self) -> Point {
+        self.orientation.rotate_point(&Point::new(0.0, 1.0, 0.0))
+    }
+
+    /// Turns the camera to face `target`, keeping `up` towards the top of the screen.
+    pub fn look_at(&mut self, target: Point, up: Point) {
+        let forward = (target - self.pos).normalize();
+        if forward.length() == 0.0 {
+            return;
+        }
+
+        // Pick another reference when looking straight along `up`
+        let mut right = up.cross(forward);
+        if right.length() < 1e-6 {
+            right = Point::new(0.0, 0.0, 1.0).cross(forward);
+            if right.length() < 1e-6 {
+                right = Point::new(1.0, 0.0, 0.0).cross(forward);
+            }
+        }
+        let right = right.normalize();
+        let up = forward.cross(right);
+
+        let mut basis = Matrix4x4::identity();
+        for (col, axis) in [right, up, forward].iter().enumerate() {
+            basis.data[0][col] = axis.x;
+            basis.data[1][col] = axis.y;
+            basis.data[2][col] = axis.z;
+        }
+        self.orientation = Quaternion::from_matrix(&basis);
+    }
+
+    /// Turns left or right around the camera's own up axis.
+    pub fn yaw(&mut self, angle: f32) {
+        self.rotate_local(Point::new(0.0, 1.0, 0.0), angle);
+    }
+
+    /// Tilts up or down around the camera's own right axis.
+    pub fn pitch(&mut self, angle: f32) {
+        self.rotate_local(Point::new(1.0, 0.0, 0.0), angle);
+    }
+
+    /// Rolls around the viewing direction.
+    pub fn roll(&mut self, angle: f32) {
+        self.rotate_local(Point::new(0.0, 0.0, 1.0), angle);
+    }
+
+    fn rotate_local(&mut self, axis: Point, angle: f32) {
+        let step = Quaternion::from_axis_angle(axis, angle);
+        self.orientation = self.orientation.multiply(&step).normalize();
+    }
+
+    /// Moves along the camera's own right, up and forward axes.
+    pub fn move_local(&mut self, right: f32, up: f32, forward: f32) {
+        self.pos += self.right() * right + self.up() * up + self.forward() * forward;
+    }
+
+    /// Camera-to-clip matrix. Visible points end up with `-w <= x, y <= w` and
+    /// `0 <= z <= w`, so depth after the divide runs from 0 at `near` to 1 at `far`.
+    pub fn projection_matrix(&self) -> Matrix4x4 {
+        let aspect = self.width as f32 / self.height as f32;
+        let (n, f) = (self.near, self.far);
+
+        match self.projection {
+            Projection::Perspective { fov } => {
+                let scale = 1.0 / (fov.to_radians() / 2.0).tan();
+                Matrix4x4 {
+                    data: [
+                        [scale / aspect, 0.0, 0.0, 0.0],
+                        [0.0, scale, 0.0, 0.0],
+                        [0.0, 0.0, f / (f - n), -n * f / (f - n)],
+                        [0.0, 0.0, 1.0, 0.0],
+                    ],
+                }
+            }
+            Projection::Orthographic { height } => {
+                let width = height * aspect;
+                Matrix4x4 {
+                    data: [
+                        [2.0 / width, 0.0, 0.0, 0.0],
+                        [0.0, 2.0 / height, 0.0, 0.0],
+                        [0.0, 0.0, 1.0 / (f - n), -n / (f - n)],
+                        [0.0, 0.0, 0.0, 1.0],
+                    ],
+                }
+            }
+            Projection::Frustum {
+                left,
+                right,
+                bottom,
+                top,
+            } => Matrix4x4 {
+                data: [
+                    [
+                        2.0 * n / (right - left),
+                        0.0,
+                        -(right + left) / (right - left),
+                        0.0,
+                    ],
+                    [
+                        0.0,
+                        2.0 * n / (top - bottom),
+                        -(top + bottom) / (top - bottom),
+                        0.0,
+                    ],
+                    [0.0, 0.0, f / (f - n), -n * f / (f - n)],
+                    [0.0, 0.0, 1.0, 0.0],
+                ],
+            },
+        }
+    }
+
+    /// World-to-clip matrix.
+    pub fn view_projection(&self) -> Matrix4x4 {
+        self.projection_matrix().multiply(&self.view_matrix())
+    }
+
+    /// World-to-camera matrix.
+    pub fn view_matrix(&self) -> Matrix4x4 {
+        let rotation = self.orientation.to_matrix().transpose();
+        rotation.multiply(&Matrix4x4::translate(-self.pos.x, -self.pos.y, -self.pos.z))
+    }
 }
 
 pub struct Space {
-    pub view: crate::window::View,
-    objects: HashMap<usize, super::object::Object>,
+    pub view: Box<dyn crate::window::Display>,
+    objects: BTreeMap<usize, super::object::Object>,
     camera: Camera,
+    lights: Vec<super::light::Light>,
+    target: super::render::RenderTarget,
+    screenshot_key: Option<crate::window::Key>,
+    screenshot_key_down: bool,
+    error: Option<crate::image::ImageError>,
+    recorder: Option<super::recorder::Recorder>,
+    last_update: Option<std::time::Instant>,
+    delta_time: f32,
 }
 
 impl Space {
     pub fn new(width: usize, height: usize, fps: usize) -> Self {
-        let view = crate::window::View::new(width, height, fps);
-        let objects = HashMap::new();
-        let camera = Camera::new(width, height);
+        Self::with_display(Box::new(crate::window::View::new(width, height, fps)))
+    }
+
+    /// Creates a space that renders into an off-screen framebuffer.
+    pub fn headless(width: usize, height: usize) -> Self {
+        Self::with_display(Box::new(crate::window::Headless::new(width, height)))
+    }
+
+    pub fn with_display(view: Box<dyn crate::window::Display>) -> Self {
+        let objects = BTreeMap::new();
+        let camera = Camera::new(view.width(), view.height());
+        let target = super::render::RenderTarget::new(
+            view.width(),
+            view.height(),
+            super::render::AntiAliasing::None,
+        );
 
         Self {
             view,
             objects,
             camera,
+            lights: Vec::new(),
+            target,
+            screenshot_key: None,
+            screenshot_key_down: false,
+            error: None,
+            recorder: None,
+            last_update: None,
+            delta_time: 0.0,
         }
     }
 
@@ -61,6 +248,215 @@ impl Space {
         id
     }
 
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Adds a light to the scene and returns its id.
+    pub fn add_light(&mut self, light: super::light::Light) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn light_mut(&mut self, id: usize) -> Option<&mut super::light::Light> {
+        self.lights.get_mut(id)
+    }
+
+    pub fn lights(&self) -> &[super::light::Light] {
+        &self.lights
+    }
+
+    pub fn set_shader(&mut self, id: usize, shader: Box<dyn super::shader::Shader>) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.shader = shader;
+        }
+    }
+
+    pub fn set_material(&mut self, id: usize, material: super::shader::Material) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.material = material;
+        }
+    }
+
+    pub fn set_color(&mut self, id: usize, color: super::shader::Color) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.color = color;
+        }
+    }
+
+    /// Sets one color per point of the object, or clears them with `None`.
+    pub fn set_vertex_colors(&mut self, id: usize, colors: Option<Vec<super::shader::Color>>) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.vertex_colors = colors;
+        }
+    }
+
+    pub fn set_texture(
+        &mut self,
+        id: usize,
+        texture: Option<std::rc::Rc<super::texture::Texture>>,
+    ) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.texture = texture;
+        }
+    }
+
+    pub fn set_edge_color(&mut self, id: usize, color: Option<super::shader::Color>) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.edge_color = color;
+        }
+    }
+
+    pub fn set_line_style(&mut self, id: usize, style: super::render::LineStyle) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.line_style = style;
+        }
+    }
+
+    pub fn set_shading(&mut self, id: usize, shading: super::shader::ShadingMode) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.shading = shading;
+        }
+    }
+
+    pub fn set_render_mode(&mut self, id: usize, mode: super::render::RenderMode) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.render_mode = mode;
+        }
+    }
+
+    pub fn set_cull_mode(&mut self, id: usize, mode: super::render::CullMode) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.cull_mode = mode;
+        }
+    }
+
+    pub fn set_front_face(&mut self, id: usize, front_face: super::render::FrontFace) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.front_face = front_face;
+        }
+    }
+
+    pub fn set_blend_mode(&mut self, id: usize, mode: super::shader::BlendMode) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.blend_mode = mode;
+        }
+    }
+
+    pub fn antialiasing(&self) -> super::render::AntiAliasing {
+        self.target.antialiasing
+    }
+
+    /// Switches how filled geometry is antialiased, reallocating the render target.
+    pub fn set_antialiasing(&mut self, antialiasing: super::render::AntiAliasing) {
+        let mut target =
+            super::render::RenderTarget::new(self.view.width(), self.view.height(), antialiasing);
+        target.linear_blending = self.target.linear_blending;
+        target.transparency = self.target.transparency;
+        self.target = target;
+    }
+
+    /// Whether colors are blended in linear light instead of sRGB.
+    pub fn set_linear_blending(&mut self, linear: bool) {
+        self.target.linear_blending = linear;
+    }
+
+    pub fn set_transparency(&mut self, transparency: super::render::Transparency) {
+        self.target.transparency = transparency;
+    }
+
+    /// Saves what was last presented, in the format matching the extension
+    /// of `path`.
+    pub fn screenshot(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), crate::image::ImageError> {
+        self.frame().save(path)
+    }
+
+    // The contents of the display buffer
+    fn frame(&self) -> crate::image::Image {
+        crate::image::Image::from_buffer(
+            self.view.width(),
+            self.view.height(),
+            self.view.buffer(),
+            self.view.pixel_format(),
+        )
+    }
+
+    /// Writes the current view as resolution-independent SVG, with the
+    /// default `SvgOptions`.
+    pub fn export_svg(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        self.export_svg_with(path, &super::svg::SvgOptions::default())
+    }
+
+    pub fn export_svg_with(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        options: &super::svg::SvgOptions,
+    ) -> std::io::Result<()> {
+        let svg = super::svg::render_svg(
+            self.objects.values(),
+            &self.camera,
+            &self.lights,
+            self.view.width(),
+            self.view.height(),
+            options,
+        );
+        std::fs::write(path, svg)
+    }
+
+    /// Key that saves a screenshot when pressed during `update`, to the first
+    /// free `screenshot-NNNN.png` in the working directory. Failures are kept
+    /// for `take_error`.
+    pub fn set_screenshot_key(&mut self, key: Option<crate::window::Key>) {
+        self.screenshot_key = key;
+    }
+
+    /// Returns and clears the last error from saving a screenshot or capturing
+    /// a recorded frame in `update`.
+    pub fn take_error(&mut self) -> Option<crate::image::ImageError> {
+        self.error.take()
+    }
+
+    /// Captures every following `update` with `recorder`, finishing any
+    /// recording already in progress. The new recording starts even when
+    /// finishing the previous one fails, which is returned as the error.
+    pub fn start_recording(
+        &mut self,
+        recorder: super::recorder::Recorder,
+    ) -> Result<(), crate::image::ImageError> {
+        let finished = self.stop_recording();
+        self.recorder = Some(recorder);
+        finished.map(|_| ())
+    }
+
+    /// Finishes the recording and returns how many frames it holds, or 0 if
+    /// nothing was being recorded.
+    pub fn stop_recording(&mut self) -> Result<usize, crate::image::ImageError> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(0),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Seconds to advance animations by for the next frame: the fixed frame
+    /// time while recording, otherwise the time between the last two updates.
+    pub fn delta_time(&self) -> f32 {
+        match &self.recorder {
+            Some(recorder) => recorder.frame_time(),
+            None => self.delta_time,
+        }
+    }
+
     pub fn rotate_object(&mut self, id: usize, x_angle: f32, y_angle: f32, z_angle: f32) {
         if let Some(obj) = self.objects.get_mut(&id) {
             if x_angle != 0.0 {
@@ -75,6 +471,43 @@ impl Space {
         }
     }
 
+    /// Sets the rotation of an object to Euler angles in radians.
+    pub fn set_object_rotation(&mut self, id: usize, x_angle: f32, y_angle: f32, z_angle: f32) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.set_rotation(x_angle, y_angle, z_angle);
+        }
+    }
+
+    pub fn set_object_orientation(&mut self, id: usize, rotation: super::math::Quaternion) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.set_orientation(rotation);
+        }
+    }
+
+    pub fn translate_object(&mut self, id: usize, x: f32, y: f32, z: f32) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.translate(x, y, z);
+        }
+    }
+
+    pub fn set_object_position(&mut self, id: usize, x: f32, y: f32, z: f32) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.set_position(super::object::Point { x, y, z });
+        }
+    }
+
+    pub fn scale_object(&mut self, id: usize, x: f32, y: f32, z: f32) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.scale(x, y, z);
+        }
+    }
+
+    pub fn set_object_transform(&mut self, id: usize, transform: super::object::Matrix4x4) {
+        if let Some(obj) = self.objects.get_mut(&id) {
+            obj.set_transform(transform);
+        }
+    }
+
     pub fn rotate_all(&mut self, x_angle: f32, y_angle: f32, z_angle: f32) {
         for (_, obj) in self.objects.iter_mut() {
             if x_angle != 0.0 {
@@ -91,26 +524,188 @@ impl Space {
 
     pub fn update(&mut self) {
         // Clear the buffer
-        self.view.buffer.fill(0);
-
-        for object in &self.objects {
-            let color = super::shader::Color {
-                r: 255,
-                g: 255,
-                b: 255,
-                a: 255,
-            };
-
-            let buffer = super::shader::Shader::render(&object.1, &color, &self.camera);
-
-            // Make sure we're copying to the correct buffer size
-            for (i, color) in buffer.iter().enumerate() {
-                if i < self.view.buffer.len() {
-                    self.view.buffer[i] = super::shader::Shader::blend(self.view.buffer[i], *color);
+        self.view.buffer_mut().fill(0);
+        self.target.clear();
+
+        // Opaque objects are drawn in id order into one shared color and depth
+        // target, translucent ones after them
+        super::render::render_scene(
+            self.objects.values(),
+            &self.camera,
+            &self.lights,
+            &mut self.target,
+        );
+
+        // Make sure we're copying to the correct buffer size
+        let resolved = self.target.resolve();
+        let format = self.view.pixel_format();
+        let buffer = self.view.buffer_mut();
+        for (i, color) in resolved.iter().enumerate() {
+            if i < buffer.len() {
+                buffer[i] = super::render::blend(buffer[i], *color, format);
+            }
+        }
+
+        self.view.present();
+
+        let now = std::time::Instant::now();
+        if let Some(last) = self.last_update {
+            self.delta_time = now.duration_since(last).as_secs_f32();
+        }
+        self.last_update = Some(now);
+
+        // A failed capture ends the recording, keeping the frames written so
+        // far playable. The capture error is the one worth reporting.
+        if let Some(mut recorder) = self.recorder.take() {
+            match recorder.capture(&self.frame()) {
+                Ok(()) => self.recorder = Some(recorder),
+                Err(err) => {
+                    let _ = recorder.finish();
+                    self.error = Some(err);
                 }
             }
         }
 
-        self.view.update();
+        // Only on the frame the key goes down, not while it is held
+        if let Some(key) = self.screenshot_key {
+            let down = self.view.is_key_down(key);
+            if down && !self.screenshot_key_down {
+                let mut n = 0;
+                let path = loop {
+                    let path = format!("screenshot-{n:04}.png");
+                    if !std::path::Path::new(&path).exists() {
+                        break path;
+                    }
+                    n += 1;
+                };
+                if let Err(err) = self.screenshot(&path) {
+                    self.error = Some(err);
+                }
+            }
+            self.screenshot_key_down = down;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_close(a: Point, b: Point) {
+        assert!((a - b).length() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    // Position after the perspective divide: x and y in -1..=1, z in 0..=1
+    fn ndc(camera: &Camera, point: Point) -> Point {
+        (camera.view_projection() * point.extend(1.0)).project()
+    }
+
+    #[test]
+    fn look_at_centers_the_target() {
+        let mut camera = Camera::new(320, 200);
+        camera.pos = Point::new(10.0, 20.0, -30.0);
+        let target = Point::new(-5.0, 3.0, 40.0);
+        camera.look_at(target, Point::new(0.0, 1.0, 0.0));
+
+        let center = ndc(&camera, target);
+        assert!(center.x.abs() < 1e-4 && center.y.abs() < 1e-4, "{center:?}");
+        assert!(center.z > 0.0 && center.z < 1.0);
+        assert_close(camera.forward(), (target - camera.pos).normalize());
+        // Above the target in the world is above it on screen
+        assert!(ndc(&camera, target + Point::new(0.0, 5.0, 0.0)).y > 0.0);
+        assert!(camera.right().y.abs() < 1e-5);
+    }
+
+    #[test]
+    fn rotations_turn_around_the_cameras_own_axes() {
+        let mut camera = Camera::new(100, 100);
+        camera.yaw(FRAC_PI_2);
+        assert_close(camera.forward(), Point::new(1.0, 0.0, 0.0));
+        assert_close(camera.right(), Point::new(0.0, 0.0, -1.0));
+        assert_close(camera.up(), Point::new(0.0, 1.0, 0.0));
+
+        // Pitch turns around the right axis left by the yaw
+        let right = camera.right();
+        camera.pitch(FRAC_PI_2);
+        assert_close(camera.right(), right);
+        assert_close(camera.forward(), Point::new(0.0, -1.0, 0.0));
+
+        // Roll keeps looking the same way
+        let forward = camera.forward();
+        camera.roll(0.7);
+        assert_close(camera.forward(), forward);
+
+        // Steps around the same axis add up
+        let mut a = Camera::new(100, 100);
+        a.yaw(0.3);
+        a.yaw(0.4);
+        let mut b = Camera::new(100, 100);
+        b.yaw(0.7);
+        assert_close(a.forward(), b.forward());
+    }
+
+    #[test]
+    fn orthographic_size_does_not_depend_on_depth() {
+        let mut camera = Camera::new(200, 100);
+        camera.projection = Projection::Orthographic { height: 50.0 };
+        let width_at = |z: f32| {
+            ndc(&camera, Point::new(5.0, 5.0, z)) - ndc(&camera, Point::new(-5.0, -5.0, z))
+        };
+        let (near, far) = (width_at(10.0), width_at(5000.0));
+        assert!((near.x - far.x).abs() < 1e-5 && (near.y - far.y).abs() < 1e-5);
+        // Ten units of the fifty high view, and of the hundred wide one
+        assert!((near.y - 0.4).abs() < 1e-5 && (near.x - 0.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn symmetric_frustum_matches_perspective() {
+        let mut perspective = Camera::new(300, 200);
+        perspective.near = 2.0;
+        perspective.projection = Projection::Perspective { fov: 50.0 };
+
+        let top = perspective.near * (25f32).to_radians().tan();
+        let right = top * 1.5;
+        let mut frustum = Camera::new(300, 200);
+        frustum.near = 2.0;
+        frustum.projection = Projection::Frustum {
+            left: -right,
+            right,
+            bottom: -top,
+            top,
+        };
+
+        let (a, b) = (perspective.projection_matrix(), frustum.projection_matrix());
+        for (row_a, row_b) in a.data.iter().zip(b.data) {
+            for (x, y) in row_a.iter().zip(row_b) {
+                assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a.data, b.data);
+            }
+        }
+    }
+
+    #[test]
+    fn depth_runs_from_near_to_far() {
+        let projections = [
+            Projection::Perspective { fov: 60.0 },
+            Projection::Orthographic { height: 10.0 },
+            Projection::Frustum {
+                left: -1.0,
+                right: 2.0,
+                bottom: -0.5,
+                top: 1.0,
+            },
+        ];
+        for projection in projections {
+            let mut camera = Camera::new(100, 100);
+            camera.pos = Point::default();
+            camera.near = 0.5;
+            camera.far = 200.0;
+            camera.projection = projection;
+
+            let depth = |z: f32| ndc(&camera, Point::new(0.0, 0.0, z)).z;
+            assert!(depth(0.5).abs() < 1e-5, "{projection:?}");
+            assert!((depth(200.0) - 1.0).abs() < 1e-5, "{projection:?}");
+            assert!(depth(10.0) > depth(5.0), "{projection:?}");
+        }
     }
 }
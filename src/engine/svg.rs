@@ -0,0 +1,688 @@
+use std::fmt::Write;
+
+use super::clip;
+use super::math::{Vec2, Vec4};
+use super::render::{Prepared, centroid, face_normal, to_screen};
+use super::shader::{BlendMode, Color, Fragment, UV_VARYINGS, Varyings};
+
+/// Choices for `Space::export_svg_with`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvgOptions {
+    /// Hides the parts of edges behind any face, including the faces of
+    /// wireframe objects, which otherwise hide nothing.
+    pub hidden_line_removal: bool,
+    /// Painted behind everything. `None` leaves the background transparent.
+    pub background: Option<Color>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            hidden_line_removal: false,
+            background: Some(Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 255,
+            }),
+        }
+    }
+}
+
+impl SvgOptions {
+    pub fn with_hidden_line_removal(mut self, remove: bool) -> Self {
+        self.hidden_line_removal = remove;
+        self
+    }
+
+    pub fn with_background(mut self, background: Option<Color>) -> Self {
+        self.background = background;
+        self
+    }
+}
+
+/// Draws the objects as an SVG document of `width` by `height` pixels.
+///
+/// Filled faces are flat shaded and painted from the back to the front. Edges
+/// come last, cut where they pass behind faces that a raster render would put
+/// in the depth buffer.
+pub fn render_svg<'a>(
+    objects: impl IntoIterator<Item = &'a super::object::Object>,
+    cam: &super::space::Camera,
+    lights: &[super::light::Light],
+    width: usize,
+    height: usize,
+    options: &SvgOptions,
+) -> String {
+    let prepared: Vec<Prepared> = objects
+        .into_iter()
+        .map(|object| Prepared::new(object, cam, lights))
+        .collect();
+
+    let mut svg = String::new();
+    svg.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">"
+    );
+    if let Some(background) = options.background {
+        let _ = writeln!(
+            svg,
+            "<rect width=\"100%\" height=\"100%\" fill=\"{}\"{}/>",
+            hex(background),
+            opacity("fill-opacity", background.a)
+        );
+    }
+
+    // Faces in painter's order, by the view depth of their centroids
+    let mut faces: Vec<(f32, &Prepared, usize)> = prepared
+        .iter()
+        .filter(|p| p.object.render_mode.colors_faces())
+        .flat_map(|p| (0..p.object.triangles.len()).map(move |i| (p.depth(i), p, i)))
+        .collect();
+    faces.sort_by(|a, b| b.0.total_cmp(&a.0));
+    if !faces.is_empty() {
+        svg.push_str("<g stroke-linejoin=\"round\">\n");
+        for (_, p, index) in faces {
+            write_face(&mut svg, p, index, width, height);
+        }
+        svg.push_str("</g>\n");
+    }
+
+    let hides_edges = |p: &Prepared| {
+        let mode = p.object.render_mode;
+        // Translucent faces are drawn without writing depth
+        options.hidden_line_removal
+            || mode.fills() && !(p.object.is_translucent() && mode.colors_faces())
+    };
+    let occluders: Vec<Occluder> = prepared
+        .iter()
+        .filter(|p| hides_edges(p))
+        .flat_map(|p| {
+            (0..p.object.triangles.len()).filter_map(|i| Occluder::new(p, i, width, height))
+        })
+        .collect();
+
+    let mut gradients = 0;
+    for p in prepared
+        .iter()
+        .filter(|p| p.object.render_mode.draws_edges())
+    {
+        write_edges(&mut svg, p, &occluders, &mut gradients, width, height);
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Writes one triangle, clipped and culled as when rasterizing, in the color
+// its shader gives the centroid
+fn write_face(svg: &mut String, p: &Prepared, index: usize, width: usize, height: usize) {
+    let object = p.object;
+    let polygon = p.clip(index);
+    if polygon.len() < 3 {
+        return;
+    }
+    let screen: Vec<Vec4> = polygon
+        .iter()
+        .map(|v| to_screen(v.position, width, height))
+        .collect();
+    let Some(front_facing) = p.facing(&screen) else {
+        return;
+    };
+
+    let triangle = &object.triangles[index];
+    let corners = [triangle.a, triangle.b, triangle.c].map(|i| &p.outputs[i]);
+    let mut varyings = corners.map(|o| o.varyings);
+    for (v, uv) in varyings.iter_mut().zip(object.triangle_uvs(index)) {
+        v.set_vec2(UV_VARYINGS, uv);
+    }
+    let third = 1.0 / 3.0;
+    let varyings = Varyings::interpolate3([&varyings[0], &varyings[1], &varyings[2]], [third; 3]);
+    let count = screen.len() as f32;
+    let fragment = Fragment {
+        x: (screen.iter().map(|v| v.x).sum::<f32>() / count) as usize,
+        y: (screen.iter().map(|v| v.y).sum::<f32>() / count) as usize,
+        uv: varyings.vec2(UV_VARYINGS),
+        varyings,
+        uv_dx: Vec2::default(),
+        uv_dy: Vec2::default(),
+        face_normal: face_normal(corners.map(|o| o.position)),
+        centroid: centroid(corners.map(|o| o.position)),
+        front_facing,
+    };
+    let mut color = object.shader.fragment(&fragment, &p.uniforms);
+    if object.blend_mode == BlendMode::Premultiplied && color.a > 0 {
+        let unpremultiply = |c: u8| (c as u32 * 255 / color.a as u32).min(255) as u8;
+        color = Color {
+            r: unpremultiply(color.r),
+            g: unpremultiply(color.g),
+            b: unpremultiply(color.b),
+            a: color.a,
+        };
+    }
+
+    svg.push_str("<polygon points=\"");
+    for (i, v) in screen.iter().enumerate() {
+        let separator = if i == 0 { "" } else { " " };
+        let _ = write!(svg, "{separator}{},{}", number(v.x), number(v.y));
+    }
+    let fill = hex(color);
+    let _ = write!(svg, "\" fill=\"{fill}\"");
+    if color.a == 255 && object.blend_mode == BlendMode::Alpha {
+        // Covers the hairline gaps renderers leave between adjacent polygons
+        let _ = write!(svg, " stroke=\"{fill}\" stroke-width=\"0.5\"");
+    } else {
+        svg.push_str(&opacity("fill-opacity", color.a));
+    }
+    svg.push_str(&blend_style(object.blend_mode));
+    svg.push_str("/>\n");
+}
+
+// Writes the visible parts of an object's edges as a group sharing its line style
+fn write_edges(
+    svg: &mut String,
+    p: &Prepared,
+    occluders: &[Occluder],
+    gradients: &mut usize,
+    width: usize,
+    height: usize,
+) {
+    let object = p.object;
+    let style = &object.line_style;
+
+    let _ = write!(
+        svg,
+        "<g fill=\"none\" stroke-width=\"{}\"",
+        number(style.width)
+    );
+    if style.dash.is_empty() {
+        svg.push_str(" stroke-linecap=\"round\"");
+    } else {
+        // Butt caps, so dashes keep the lengths they have in a raster render
+        let dash: Vec<String> = style.dash.iter().map(|&d| number(d)).collect();
+        let _ = write!(svg, " stroke-dasharray=\"{}\"", dash.join(" "));
+    }
+    svg.push_str(&blend_style(object.blend_mode));
+    svg.push_str(">\n");
+
+    for edge in &object.edges {
+        let (a, b) = (p.clip_points[edge.start], p.clip_points[edge.end]);
+        let Some((t0, t1)) = clip::clip_line(a, b) else {
+            continue;
+        };
+
+        let (start_color, end_color) = match object.edge_color {
+            Some(color) => (color, color),
+            None => (
+                object.vertex_color(edge.start),
+                object.vertex_color(edge.end),
+            ),
+        };
+        let (start_color, end_color) = (
+            start_color.lerp(&end_color, t0),
+            start_color.lerp(&end_color, t1),
+        );
+
+        let start = to_screen(a.lerp(b, t0), width, height);
+        let end = to_screen(a.lerp(b, t1), width, height);
+
+        // Faces the edge lies on cannot hide it
+        let occluders = occluders
+            .iter()
+            .filter(|o| !(o.object == object.id && o.has_edge(edge.start, edge.end)));
+
+        let length = (end.x - start.x).hypot(end.y - start.y);
+        for (s0, s1) in visible_parts(start, end, occluders) {
+            // Drop slivers left between neighbouring faces
+            if (s1 - s0) * length < 0.05 {
+                continue;
+            }
+            let (from, to) = (start.lerp(end, s0), start.lerp(end, s1));
+            let (from_color, to_color) = (
+                start_color.lerp(&end_color, s0),
+                start_color.lerp(&end_color, s1),
+            );
+
+            let stroke = if from_color == to_color {
+                format!(
+                    "stroke=\"{}\"{}",
+                    hex(from_color),
+                    opacity("stroke-opacity", from_color.a)
+                )
+            } else {
+                *gradients += 1;
+                let id = format!("edge-{gradients}");
+                let _ = writeln!(
+                    svg,
+                    "<linearGradient id=\"{id}\" gradientUnits=\"userSpaceOnUse\" \
+                     x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\">\
+                     <stop offset=\"0\" stop-color=\"{}\"{}/>\
+                     <stop offset=\"1\" stop-color=\"{}\"{}/></linearGradient>",
+                    number(from.x),
+                    number(from.y),
+                    number(to.x),
+                    number(to.y),
+                    hex(from_color),
+                    opacity("stop-opacity", from_color.a),
+                    hex(to_color),
+                    opacity("stop-opacity", to_color.a),
+                );
+                format!("stroke=\"url(#{id})\"")
+            };
+
+            let _ = write!(
+                svg,
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" {stroke}",
+                number(from.x),
+                number(from.y),
+                number(to.x),
+                number(to.y),
+            );
+            // Keep the dash pattern running across the gaps
+            if !style.dash.is_empty() && s0 > 0.0 {
+                let _ = write!(svg, " stroke-dashoffset=\"{}\"", number(s0 * length));
+            }
+            svg.push_str("/>\n");
+        }
+    }
+    svg.push_str("</g>\n");
+}
+
+// A face that hides edges behind it, as its outline on screen and the plane
+// its depth lies in
+struct Occluder {
+    object: usize,
+    corners: [usize; 3],
+    points: Vec<Vec4>,
+    min: Vec2,
+    max: Vec2,
+    // Depth as `z = x * dz_dx + y * dz_dy + z0`
+    dz_dx: f32,
+    dz_dy: f32,
+    z0: f32,
+}
+
+impl Occluder {
+    fn new(p: &Prepared, index: usize, width: usize, height: usize) -> Option<Occluder> {
+        let polygon = p.clip(index);
+        if polygon.len() < 3 {
+            return None;
+        }
+        let mut points: Vec<Vec4> = polygon
+            .iter()
+            .map(|v| to_screen(v.position, width, height))
+            .collect();
+        p.facing(&points)?;
+
+        // Screen depth is linear across a flat face
+        let (a, b, c) = (points[0], points[1], points[2]);
+        let (u, v) = (b - a, c - a);
+        let nx = u.y * v.z - u.z * v.y;
+        let ny = u.z * v.x - u.x * v.z;
+        let nz = u.x * v.y - u.y * v.x;
+        // Seen edge-on
+        if nz.abs() < 1e-6 {
+            return None;
+        }
+        let (dz_dx, dz_dy) = (-nx / nz, -ny / nz);
+
+        // Counter-clockwise on screen, so the inside is to the left of each side
+        if super::render::polygon_area(&points) < 0.0 {
+            points.reverse();
+        }
+        let min = points.iter().fold(Vec2::new(f32::MAX, f32::MAX), |m, v| {
+            Vec2::new(m.x.min(v.x), m.y.min(v.y))
+        });
+        let max = points.iter().fold(Vec2::new(f32::MIN, f32::MIN), |m, v| {
+            Vec2::new(m.x.max(v.x), m.y.max(v.y))
+        });
+
+        let triangle = &p.object.triangles[index];
+        Some(Occluder {
+            object: p.object.id,
+            corners: [triangle.a, triangle.b, triangle.c],
+            min,
+            max,
+            dz_dx,
+            dz_dy,
+            z0: a.z - a.x * dz_dx - a.y * dz_dy,
+            points,
+        })
+    }
+
+    fn has_edge(&self, start: usize, end: usize) -> bool {
+        self.corners.contains(&start) && self.corners.contains(&end)
+    }
+
+    // The range of the segment, as fractions of its length, that is behind the face
+    fn hidden(&self, start: Vec4, end: Vec4) -> Option<(f32, f32)> {
+        if start.x.max(end.x) < self.min.x
+            || start.x.min(end.x) > self.max.x
+            || start.y.max(end.y) < self.min.y
+            || start.y.min(end.y) > self.max.y
+        {
+            return None;
+        }
+
+        // Part of the segment inside every side of the outline
+        let (mut lo, mut hi) = (0.0f32, 1.0f32);
+        let mut limit = |f0: f32, f1: f32| {
+            // Inside where f0 + (f1 - f0) * s >= 0
+            if f0 < 0.0 && f1 < 0.0 {
+                hi = -1.0;
+            } else if f0 < 0.0 {
+                lo = lo.max(f0 / (f0 - f1));
+            } else if f1 < 0.0 {
+                hi = hi.min(f0 / (f0 - f1));
+            }
+        };
+        for (i, a) in self.points.iter().enumerate() {
+            let b = self.points[(i + 1) % self.points.len()];
+            // Positive to the left of a -> b, with y pointing down
+            let side = |v: Vec4| (b.x - a.x) * (a.y - v.y) - (b.y - a.y) * (a.x - v.x);
+            limit(side(start), side(end));
+        }
+
+        // Of that, the part where the line is farther away than the face
+        let behind = |v: Vec4| v.z - (v.x * self.dz_dx + v.y * self.dz_dy + self.z0);
+        let (d0, d1) = (behind(start), behind(end));
+        limit(d0, d1);
+
+        (lo < hi).then_some((lo, hi))
+    }
+}
+
+// Ranges of the segment from `start` to `end` that no occluder hides
+fn visible_parts<'a>(
+    start: Vec4,
+    end: Vec4,
+    occluders: impl Iterator<Item = &'a Occluder>,
+) -> Vec<(f32, f32)> {
+    let mut hidden: Vec<(f32, f32)> = occluders.filter_map(|o| o.hidden(start, end)).collect();
+    hidden.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut visible = Vec::new();
+    let mut from = 0.0;
+    for (lo, hi) in hidden {
+        if lo > from {
+            visible.push((from, lo));
+        }
+        from = f32::max(from, hi);
+    }
+    if from < 1.0 {
+        visible.push((from, 1.0));
+    }
+    visible
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+// An opacity attribute, left out when fully opaque
+fn opacity(attribute: &str, alpha: u8) -> String {
+    if alpha == 255 {
+        String::new()
+    } else {
+        format!(" {attribute}=\"{}\"", number(alpha as f32 / 255.0))
+    }
+}
+
+// The CSS blend mode closest to each of the engine's
+fn blend_style(mode: BlendMode) -> String {
+    let css = match mode {
+        BlendMode::Alpha | BlendMode::Premultiplied => return String::new(),
+        BlendMode::Additive => "plus-lighter",
+        BlendMode::Multiply => "multiply",
+        BlendMode::Screen => "screen",
+        BlendMode::Min => "darken",
+        BlendMode::Max => "lighten",
+    };
+    format!(" style=\"mix-blend-mode:{css}\"")
+}
+
+// Coordinates to a hundredth of a pixel, without trailing zeros
+fn number(value: f32) -> String {
+    let text = format!("{value:.2}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".into(),
+        _ => text.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::object::{Edge, Object, Point};
+    use crate::engine::render::{LineStyle, RenderMode};
+    use crate::engine::space::Camera;
+
+    const SIZE: usize = 200;
+
+    const RED: Color = Color {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+
+    fn render(objects: &[Object], options: &SvgOptions) -> String {
+        render_svg(objects, &Camera::new(SIZE, SIZE), &[], SIZE, SIZE, options)
+    }
+
+    // Every element of the document with the tag `tag`
+    fn elements<'a>(svg: &'a str, tag: &str) -> Vec<&'a str> {
+        let start = format!("{tag} ");
+        svg.split('<').filter(|e| e.starts_with(&start)).collect()
+    }
+
+    fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+        let key = format!(" {name}=\"");
+        let start = element.find(&key)? + key.len();
+        let end = start + element[start..].find('"')?;
+        Some(&element[start..end])
+    }
+
+    fn number_attribute(element: &str, name: &str) -> f32 {
+        attribute(element, name).unwrap().parse().unwrap()
+    }
+
+    fn line_ends(element: &str) -> [f32; 4] {
+        ["x1", "y1", "x2", "y2"].map(|name| number_attribute(element, name))
+    }
+
+    fn screen(point: Point) -> Vec4 {
+        let cam = Camera::new(SIZE, SIZE);
+        to_screen(cam.view_projection() * point.extend(1.0), SIZE, SIZE)
+    }
+
+    fn distance_to_segment(p: (f32, f32), a: Vec4, b: Vec4) -> f32 {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let t = (((p.0 - a.x) * dx + (p.1 - a.y) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+        (p.0 - a.x - t * dx).hypot(p.1 - a.y - t * dy)
+    }
+
+    // A wireframe segment between two points, behind nothing of its own
+    fn segment(id: usize, start: Point, end: Point) -> Object {
+        let mut object = Object::new_cube(id, 1.0, Point::default());
+        object.points = vec![start, end];
+        object.original_points = object.points.clone();
+        object.edges = vec![Edge { start: 0, end: 1 }];
+        object.triangles = Vec::new();
+        object.uvs = None;
+        object.color = RED;
+        object.render_mode = RenderMode::Wireframe;
+        object
+    }
+
+    // A solid cube centered in front of a segment running across the view
+    // behind it, which it hides from x 56.7 to 143.3
+    fn segment_behind_cube(style: LineStyle) -> Vec<Object> {
+        let mut cube = Object::new_cube(0, 20.0, Point::default());
+        cube.render_mode = RenderMode::Solid;
+        let mut line = segment(1, Point::new(-40.0, 0.0, 30.0), Point::new(40.0, 0.0, 30.0));
+        line.line_style = style;
+        vec![cube, line]
+    }
+
+    #[test]
+    fn hidden_line_removal_drops_the_back_edges_of_a_cube() {
+        let mut cube = Object::new_cube(0, 30.0, Point::default());
+        cube.render_mode = RenderMode::Wireframe;
+        cube.set_rotation(0.5, 0.6, 0.0);
+        let eye = Camera::new(SIZE, SIZE).pos;
+        let far = (0..8)
+            .max_by(|&a, &b| {
+                let distance = |i: usize| (cube.points[i] - eye).length();
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap();
+        let corners: Vec<Vec4> = cube.points.iter().map(|&p| screen(p)).collect();
+        let objects = [cube];
+
+        // Without removal, wireframes hide nothing
+        let all = render(&objects, &SvgOptions::default());
+        assert_eq!(elements(&all, "line").len(), 12);
+        assert!(elements(&all, "polygon").is_empty());
+
+        let svg = render(
+            &objects,
+            &SvgOptions::default().with_hidden_line_removal(true),
+        );
+        let lines = elements(&svg, "line");
+        // The edges of the far corner are gone, the others drawn whole
+        for edge in &objects[0].edges {
+            let (a, b) = (corners[edge.start], corners[edge.end]);
+            let along: Vec<[f32; 4]> = lines
+                .iter()
+                .map(|line| line_ends(line))
+                .filter(|&[x1, y1, x2, y2]| {
+                    distance_to_segment((x1, y1), a, b) < 0.05
+                        && distance_to_segment((x2, y2), a, b) < 0.05
+                })
+                .collect();
+            let length: f32 = along
+                .iter()
+                .map(|[x1, y1, x2, y2]| (x2 - x1).hypot(y2 - y1))
+                .sum();
+            let expected = if edge.start == far || edge.end == far {
+                0.0
+            } else {
+                (b.x - a.x).hypot(b.y - a.y)
+            };
+            assert!(
+                (length - expected).abs() < 0.1,
+                "edge {}-{}: {length} of {expected}",
+                edge.start,
+                edge.end
+            );
+        }
+        assert_eq!(lines.len(), 9);
+    }
+
+    #[test]
+    fn edges_partly_behind_a_face_are_split_around_it() {
+        let svg = render(
+            &segment_behind_cube(LineStyle::default()),
+            &SvgOptions::default(),
+        );
+        let lines: Vec<[f32; 4]> = elements(&svg, "line")
+            .iter()
+            .map(|l| line_ends(l))
+            .collect();
+
+        let (start, end) = (
+            screen(Point::new(-40.0, 0.0, 30.0)),
+            screen(Point::new(40.0, 0.0, 30.0)),
+        );
+        // The silhouette of the cube is its front face, 40 units away
+        let (left, right) = (
+            screen(Point::new(-10.0, 0.0, -10.0)).x,
+            screen(Point::new(10.0, 0.0, -10.0)).x,
+        );
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        for (line, (x1, x2)) in lines.iter().zip([(start.x, left), (right, end.x)]) {
+            assert!((line[0] - x1).abs() < 0.02, "{line:?}");
+            assert!((line[2] - x2).abs() < 0.02, "{line:?}");
+            assert_eq!((line[1], line[3]), (100.0, 100.0));
+        }
+    }
+
+    #[test]
+    fn polygons_are_painted_from_far_to_near() {
+        // Apart, so each also shows the side facing the other
+        let cubes = || {
+            let mut near = Object::new_cube(0, 20.0, Point::new(-20.0, 0.0, 0.0));
+            near.render_mode = RenderMode::Solid;
+            near.color = RED;
+            let mut far = Object::new_cube(1, 20.0, Point::new(20.0, 0.0, 40.0));
+            far.render_mode = RenderMode::Solid;
+            far.color = Color {
+                r: 0,
+                g: 0,
+                b: 255,
+                a: 255,
+            };
+            [near, far]
+        };
+
+        // Whatever order the objects come in
+        for reversed in [false, true] {
+            let mut objects = cubes();
+            if reversed {
+                objects.reverse();
+            }
+            let svg = render(&objects, &SvgOptions::default());
+            let fills: Vec<&str> = elements(&svg, "polygon")
+                .iter()
+                .map(|p| attribute(p, "fill").unwrap())
+                .collect();
+            // Two triangles for each of the two faces of each cube
+            assert_eq!(fills.len(), 8, "{fills:?}");
+            let first_red = fills.iter().position(|&f| f == "#ff0000").unwrap();
+            let last_blue = fills.iter().rposition(|&f| f == "#0000ff").unwrap();
+            assert!(last_blue < first_red, "{fills:?}");
+        }
+    }
+
+    #[test]
+    fn dash_attributes_follow_the_line_style() {
+        let solid = render(
+            &segment_behind_cube(LineStyle::default()),
+            &SvgOptions::default(),
+        );
+        let group = elements(&solid, "g").pop().unwrap();
+        assert_eq!(attribute(group, "stroke-linecap"), Some("round"));
+        assert_eq!(attribute(group, "stroke-dasharray"), None);
+        assert!(
+            elements(&solid, "line")
+                .iter()
+                .all(|l| !l.contains("dashoffset"))
+        );
+
+        let style = LineStyle::dashed(6.0, 2.5).with_width(2.0);
+        let dashed = render(&segment_behind_cube(style), &SvgOptions::default());
+        let group = elements(&dashed, "g").pop().unwrap();
+        assert_eq!(attribute(group, "stroke-dasharray"), Some("6 2.5"));
+        assert_eq!(attribute(group, "stroke-width"), Some("2"));
+        assert_eq!(attribute(group, "stroke-linecap"), None);
+
+        // The part after the gap carries on with the pattern where it would be
+        let lines = elements(&dashed, "line");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(attribute(lines[0], "stroke-dashoffset"), None);
+        let offset = number_attribute(lines[1], "stroke-dashoffset");
+        let travelled = line_ends(lines[1])[0] - line_ends(lines[0])[0];
+        assert!((offset - travelled).abs() < 0.02, "{offset} {travelled}");
+
+        let dotted = render(
+            &segment_behind_cube(LineStyle::dotted(3.0)),
+            &SvgOptions::default(),
+        );
+        let group = elements(&dotted, "g").pop().unwrap();
+        assert_eq!(attribute(group, "stroke-dasharray"), Some("1 3"));
+    }
+}
@@ -0,0 +1,444 @@
+use std::path::Path;
+
+use super::math::Vec2;
+use super::shader::Color;
+use crate::image::{Image, ImageError};
+
+/// How texels are looked up between their centers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    /// The closest texel of the full-size image.
+    Nearest,
+    /// A blend of the four closest texels of the full-size image.
+    #[default]
+    Bilinear,
+    /// Bilinear lookups in the two mip levels closest to the on-screen size,
+    /// blended together. Avoids shimmering on distant or slanted surfaces.
+    Trilinear,
+}
+
+/// What texture coordinates outside `0.0..=1.0` map to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wrap {
+    /// Tile the image.
+    #[default]
+    Repeat,
+    /// Tile the image, flipping every other copy.
+    MirroredRepeat,
+    /// Stretch the border texels.
+    Clamp,
+}
+
+/// An image mapped onto triangles through their texture coordinates, where
+/// `(0, 0)` is the top-left corner and `(1, 1)` the bottom-right one.
+#[derive(Debug, Clone)]
+pub struct Texture {
+    pub filter: Filter,
+    pub wrap: Wrap,
+    // Mip chain, each level half the size of the previous one; level 0 is the image
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone)]
+struct Level {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Level {
+    fn texel(&self, x: i64, y: i64, wrap: Wrap) -> Color {
+        let x = wrap_index(x, self.width, wrap);
+        let y = wrap_index(y, self.height, wrap);
+        self.pixels[y * self.width + x]
+    }
+
+    fn nearest(&self, uv: Vec2, wrap: Wrap) -> Color {
+        let x = (uv.x * self.width as f32).floor() as i64;
+        let y = (uv.y * self.height as f32).floor() as i64;
+        self.texel(x, y, wrap)
+    }
+
+    fn bilinear(&self, uv: Vec2, wrap: Wrap) -> [f32; 4] {
+        // Texel centers sit at half-integer coordinates
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = uv.y * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let mut out = [0.0; 4];
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let texel = self.texel(x0 + dx, y0 + dy, wrap);
+            let channels = [texel.r, texel.g, texel.b, texel.a];
+            for (o, c) in out.iter_mut().zip(channels) {
+                *o += c as f32 * weight;
+            }
+        }
+        out
+    }
+
+    // Box-filters every 2x2 block into one texel
+    fn downsample(&self) -> Level {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (x * 2 + dx).min(self.width - 1);
+                    let sy = (y * 2 + dy).min(self.height - 1);
+                    let texel = self.pixels[sy * self.width + sx];
+                    sum[0] += texel.r as u32;
+                    sum[1] += texel.g as u32;
+                    sum[2] += texel.b as u32;
+                    sum[3] += texel.a as u32;
+                }
+                pixels.push(Color {
+                    r: ((sum[0] + 2) / 4) as u8,
+                    g: ((sum[1] + 2) / 4) as u8,
+                    b: ((sum[2] + 2) / 4) as u8,
+                    a: ((sum[3] + 2) / 4) as u8,
+                });
+            }
+        }
+
+        Level {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+impl Texture {
+    /// Creates a texture from `width * height` pixels in rows from the top.
+    ///
+    /// Panics if `width` or `height` is zero, or if `pixels` does not hold
+    /// exactly that many colors.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "texture must not be empty");
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixel count does not match size"
+        );
+
+        let mut levels = vec![Level {
+            width,
+            height,
+            pixels,
+        }];
+        loop {
+            let last = &levels[levels.len() - 1];
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            levels.push(next);
+        }
+
+        Texture {
+            filter: Filter::default(),
+            wrap: Wrap::default(),
+            levels,
+        }
+    }
+
+    /// Reads an image file, see `Image::load`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        Self::try_from(Image::load(path)?)
+    }
+
+    /// Creates a texture whose pixel at column `x` and row `y` is `f(x, y)`.
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> Color) -> Self {
+        let pixels = (0..width * height)
+            .map(|i| f(i % width, i / width))
+            .collect();
+        Self::new(width, height, pixels)
+    }
+
+    /// A `size` by `size` checkerboard of `cells` squares per side.
+    pub fn checkerboard(size: usize, cells: usize, first: Color, second: Color) -> Self {
+        let cell = (size / cells.max(1)).max(1);
+        Self::from_fn(size, size, |x, y| {
+            if (x / cell + y / cell).is_multiple_of(2) {
+                first
+            } else {
+                second
+            }
+        })
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    /// The full-size image, in rows from the top.
+    pub fn pixels(&self) -> &[Color] {
+        &self.levels[0].pixels
+    }
+
+    /// Mip level matching how many texels a pixel step covers, given the change
+    /// of the texture coordinates to the next pixel across and down.
+    pub fn level_of_detail(&self, dx: Vec2, dy: Vec2) -> f32 {
+        let size = Vec2::new(self.width() as f32, self.height() as f32);
+        let scale = |d: Vec2| Vec2::new(d.x * size.x, d.y * size.y).length();
+        scale(dx).max(scale(dy)).max(f32::MIN_POSITIVE).log2()
+    }
+
+    /// Color at texture coordinates `uv`. `lod` is only used for trilinear
+    /// filtering, see `level_of_detail`.
+    pub fn sample(&self, uv: Vec2, lod: f32) -> Color {
+        match self.filter {
+            Filter::Nearest => self.levels[0].nearest(uv, self.wrap),
+            Filter::Bilinear => to_color(self.levels[0].bilinear(uv, self.wrap)),
+            Filter::Trilinear => {
+                let lod = lod.clamp(0.0, (self.levels.len() - 1) as f32);
+                let fine = lod.floor() as usize;
+                let coarse = (fine + 1).min(self.levels.len() - 1);
+                let t = lod - fine as f32;
+
+                let a = self.levels[fine].bilinear(uv, self.wrap);
+                let b = self.levels[coarse].bilinear(uv, self.wrap);
+                to_color([0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t))
+            }
+        }
+    }
+}
+
+/// Fails on images without pixels, which decoders accept but textures cannot
+/// be sampled from.
+impl TryFrom<Image> for Texture {
+    type Error = ImageError;
+
+    fn try_from(image: Image) -> Result<Self, ImageError> {
+        if image.width == 0 || image.height == 0 {
+            return Err(ImageError::Invalid(format!(
+                "{}x{} texture",
+                image.width, image.height
+            )));
+        }
+        if image.pixels.len() != image.width * image.height {
+            return Err(ImageError::Invalid(format!(
+                "{} pixels in a {}x{} image",
+                image.pixels.len(),
+                image.width,
+                image.height
+            )));
+        }
+        Ok(Texture::new(image.width, image.height, image.pixels))
+    }
+}
+
+fn to_color(channels: [f32; 4]) -> Color {
+    let channel = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    Color {
+        r: channel(channels[0]),
+        g: channel(channels[1]),
+        b: channel(channels[2]),
+        a: channel(channels[3]),
+    }
+}
+
+// Maps a texel index outside `0..size` back inside according to `wrap`
+fn wrap_index(i: i64, size: usize, wrap: Wrap) -> usize {
+    let size = size as i64;
+    let i = match wrap {
+        Wrap::Repeat => i.rem_euclid(size),
+        Wrap::MirroredRepeat => {
+            let i = i.rem_euclid(2 * size);
+            if i < size { i } else { 2 * size - 1 - i }
+        }
+        Wrap::Clamp => i.clamp(0, size - 1),
+    };
+    i as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::ImageFormat;
+
+    #[test]
+    fn empty_images_are_rejected() {
+        let empty = Image::new(0, 0, Vec::new());
+        for format in [ImageFormat::Ppm, ImageFormat::TgaRle, ImageFormat::Bmp32] {
+            let decoded = Image::decode(&empty.encode(format).unwrap()).unwrap();
+            assert!(Texture::try_from(decoded).is_err(), "{format:?}");
+        }
+        let wide = Image::new(4, 0, Vec::new());
+        assert!(Texture::try_from(wide).is_err());
+    }
+
+    #[test]
+    fn images_become_the_first_mip_level() {
+        let pixels = (0..6)
+            .map(|i| Color {
+                r: i * 40,
+                g: 0,
+                b: 0,
+                a: 255,
+            })
+            .collect::<Vec<_>>();
+        let texture = Texture::try_from(Image::new(3, 2, pixels.clone())).unwrap();
+        assert_eq!((texture.width(), texture.height()), (3, 2));
+        assert_eq!(texture.pixels(), &pixels[..]);
+    }
+
+    fn gray(v: u8) -> Color {
+        Color {
+            r: v,
+            g: v,
+            b: v,
+            a: 255,
+        }
+    }
+
+    // Black, 85, 170 and white texels of a 2 by 2 texture
+    fn quad(filter: Filter) -> Texture {
+        Texture::new(2, 2, vec![gray(0), gray(85), gray(170), gray(255)]).with_filter(filter)
+    }
+
+    #[test]
+    #[should_panic(expected = "texture must not be empty")]
+    fn empty_textures_panic() {
+        Texture::new(0, 3, Vec::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel count does not match size")]
+    fn mismatched_pixel_counts_panic() {
+        Texture::new(2, 2, vec![gray(0); 3]);
+    }
+
+    #[test]
+    fn nearest_picks_the_texel_under_the_coordinates() {
+        let texture = quad(Filter::Nearest);
+        let cases = [
+            (0.1, 0.1, 0),
+            (0.9, 0.1, 85),
+            (0.1, 0.9, 170),
+            (0.9, 0.9, 255),
+            // Halfway rounds toward the bottom-right texel
+            (0.5, 0.5, 255),
+        ];
+        for (u, v, expected) in cases {
+            assert_eq!(
+                texture.sample(Vec2::new(u, v), 0.0),
+                gray(expected),
+                "{u},{v}"
+            );
+        }
+    }
+
+    #[test]
+    fn bilinear_blends_the_four_closest_texels() {
+        let texture = quad(Filter::Bilinear).with_wrap(Wrap::Clamp);
+        // Exact on texel centers
+        assert_eq!(texture.sample(Vec2::new(0.25, 0.25), 0.0), gray(0));
+        assert_eq!(texture.sample(Vec2::new(0.75, 0.75), 0.0), gray(255));
+        // Halfway between the centers of all four
+        assert_eq!(texture.sample(Vec2::new(0.5, 0.5), 0.0), gray(128));
+        // A quarter of the way from the top-left texel to the one to its right
+        let quarter = 0.25 + 0.25 * 0.5;
+        assert_eq!(texture.sample(Vec2::new(quarter, 0.25), 0.0), gray(21));
+
+        // Across the border, Repeat blends with the texel on the other side
+        let repeat = quad(Filter::Bilinear);
+        assert_eq!(repeat.sample(Vec2::new(0.0, 0.25), 0.0), gray(43));
+        assert_eq!(texture.sample(Vec2::new(0.0, 0.25), 0.0), gray(0));
+    }
+
+    #[test]
+    fn trilinear_blends_between_mip_levels() {
+        // 4 by 4 black and white columns: level 1 is 2 by 2 gray, level 2 too
+        let texture = Texture::from_fn(4, 4, |x, _| gray(if x % 2 == 0 { 0 } else { 255 }))
+            .with_filter(Filter::Trilinear);
+        let center = Vec2::new(0.125, 0.125);
+        assert_eq!(texture.sample(center, 0.0), gray(0));
+        assert_eq!(texture.sample(center, 1.0), gray(128));
+        assert_eq!(texture.sample(center, 0.5), gray(64));
+        // Clamped to the existing levels
+        assert_eq!(texture.sample(center, -3.0), gray(0));
+        assert_eq!(texture.sample(center, 10.0), gray(128));
+
+        // Level 2 averages the 2 by 2 level 1 of a darker and a brighter half
+        let halves = Texture::from_fn(4, 4, |_, y| gray(if y < 2 { 40 } else { 200 }))
+            .with_filter(Filter::Trilinear)
+            .with_wrap(Wrap::Clamp);
+        let top = Vec2::new(0.5, 0.25);
+        assert_eq!(halves.sample(top, 1.0), gray(40));
+        assert_eq!(halves.sample(top, 2.0), gray(120));
+        assert_eq!(halves.sample(top, 1.5), gray(80));
+    }
+
+    #[test]
+    fn wrap_modes_map_indices_back_inside() {
+        let cases = [
+            (Wrap::Repeat, [3, 0, 1, 2, 3, 0, 1]),
+            (Wrap::MirroredRepeat, [0, 0, 1, 2, 3, 3, 2]),
+            (Wrap::Clamp, [0, 0, 1, 2, 3, 3, 3]),
+        ];
+        for (wrap, expected) in cases {
+            let wrapped = [-1, 0, 1, 2, 3, 4, 5].map(|i| wrap_index(i, 4, wrap));
+            assert_eq!(wrapped, expected, "{wrap:?}");
+        }
+        assert_eq!(wrap_index(-5, 4, Wrap::MirroredRepeat), 3);
+        assert_eq!(wrap_index(8, 4, Wrap::MirroredRepeat), 0);
+        assert_eq!(wrap_index(-9, 4, Wrap::Clamp), 0);
+        assert_eq!(wrap_index(-9, 4, Wrap::Repeat), 3);
+    }
+
+    #[test]
+    fn mip_chains_halve_down_to_one_texel() {
+        let sizes = |width, height| {
+            let texture = Texture::new(width, height, vec![gray(0); width * height]);
+            texture
+                .levels
+                .iter()
+                .map(|level| (level.width, level.height))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sizes(5, 3), [(5, 3), (2, 1), (1, 1)]);
+        assert_eq!(sizes(7, 1), [(7, 1), (3, 1), (1, 1)]);
+        assert_eq!(sizes(1, 6), [(1, 6), (1, 3), (1, 1)]);
+        assert_eq!(sizes(8, 8), [(8, 8), (4, 4), (2, 2), (1, 1)]);
+        assert_eq!(sizes(1, 1), [(1, 1)]);
+    }
+
+    #[test]
+    fn level_of_detail_is_the_log2_of_the_texel_footprint() {
+        let texture = Texture::new(64, 32, vec![gray(0); 64 * 32]);
+        let texel = 1.0 / 64.0;
+        let lod = |dx: Vec2, dy: Vec2| texture.level_of_detail(dx, dy);
+        assert_eq!(lod(Vec2::new(texel, 0.0), Vec2::new(0.0, 0.5 * texel)), 0.0);
+        assert_eq!(lod(Vec2::new(4.0 * texel, 0.0), Vec2::new(0.0, 0.0)), 2.0);
+        assert_eq!(lod(Vec2::new(0.5 * texel, 0.0), Vec2::new(0.0, 0.0)), -1.0);
+        // The larger step wins, and heights count in the texture's own texels
+        assert_eq!(lod(Vec2::new(texel, 0.0), Vec2::new(0.0, 8.0 / 32.0)), 3.0);
+        let diagonal = lod(Vec2::new(3.0 * texel, 4.0 / 32.0), Vec2::new(0.0, 0.0));
+        assert!((diagonal - 5.0f32.log2()).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,153 @@
+use super::{Image, ImageError, read_u16, read_u32, truncated};
+use crate::engine::shader::Color;
+
+const FILE_HEADER_SIZE: usize = 14;
+const INFO_HEADER_SIZE: usize = 40;
+// BITMAPV4HEADER, the first version whose channel masks include alpha
+const V4_HEADER_SIZE: usize = 108;
+
+// Compression methods
+const RGB: u32 = 0;
+const BITFIELDS: u32 = 3;
+const ALPHA_BITFIELDS: u32 = 6;
+
+/// Writes bottom-up BGR rows, or BGRA with channel masks when `alpha` is set.
+pub fn encode(image: &Image, alpha: bool) -> Vec<u8> {
+    let (header_size, bytes_per_pixel) = if alpha {
+        (V4_HEADER_SIZE, 4)
+    } else {
+        (INFO_HEADER_SIZE, 3)
+    };
+    // Rows are padded to a multiple of four bytes
+    let stride = (image.width * bytes_per_pixel + 3) & !3;
+    let data_offset = FILE_HEADER_SIZE + header_size;
+    let data_size = stride * image.height;
+
+    let mut out = Vec::with_capacity(data_offset + data_size);
+    out.extend(b"BM");
+    out.extend(((data_offset + data_size) as u32).to_le_bytes());
+    out.extend([0; 4]);
+    out.extend((data_offset as u32).to_le_bytes());
+
+    out.extend((header_size as u32).to_le_bytes());
+    out.extend((image.width as i32).to_le_bytes());
+    out.extend((image.height as i32).to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(((bytes_per_pixel * 8) as u16).to_le_bytes());
+    out.extend((if alpha { BITFIELDS } else { RGB }).to_le_bytes());
+    out.extend((data_size as u32).to_le_bytes());
+    // 72 DPI
+    out.extend(2835u32.to_le_bytes());
+    out.extend(2835u32.to_le_bytes());
+    out.extend([0; 8]);
+    if alpha {
+        for mask in [0x00FF_0000u32, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000] {
+            out.extend(mask.to_le_bytes());
+        }
+        out.extend(b"BGRs");
+        // Endpoints and gamma, unused for sRGB
+        out.extend([0; 48]);
+    }
+
+    for row in image.pixels.chunks(image.width.max(1)).rev() {
+        let start = out.len();
+        for c in row {
+            out.extend([c.b, c.g, c.r]);
+            if alpha {
+                out.push(c.a);
+            }
+        }
+        out.resize(start + stride, 0);
+    }
+    out
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+    let data_offset = read_u32(bytes, 10)? as usize;
+    let header_size = read_u32(bytes, 14)? as usize;
+    if header_size < INFO_HEADER_SIZE {
+        return Err(ImageError::Unsupported("OS/2 BMP".into()));
+    }
+    let width = read_u32(bytes, 18)? as i32;
+    let height = read_u32(bytes, 22)? as i32;
+    let depth = read_u16(bytes, 28)?;
+    let compression = read_u32(bytes, 30)?;
+
+    if width < 0 {
+        return Err(ImageError::Invalid(format!("width {width}")));
+    }
+    let top_down = height < 0;
+    let (width, height) = (width as usize, height.unsigned_abs() as usize);
+
+    let bytes_per_pixel = match depth {
+        24 | 32 => depth as usize / 8,
+        _ => return Err(ImageError::Unsupported(format!("{depth}-bit BMP"))),
+    };
+
+    // Masks of red, green, blue and alpha in a little-endian pixel
+    let masks = match (compression, depth) {
+        (RGB, _) => [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0],
+        (BITFIELDS | ALPHA_BITFIELDS, 32) => {
+            let at = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+            let alpha_mask =
+                if header_size > INFO_HEADER_SIZE + 12 || compression == ALPHA_BITFIELDS {
+                    read_u32(bytes, at + 12)?
+                } else {
+                    0
+                };
+            [
+                read_u32(bytes, at)?,
+                read_u32(bytes, at + 4)?,
+                read_u32(bytes, at + 8)?,
+                alpha_mask,
+            ]
+        }
+        _ => {
+            return Err(ImageError::Unsupported(format!(
+                "BMP compression {compression}"
+            )));
+        }
+    };
+
+    let stride = (width * bytes_per_pixel + 3) & !3;
+    let size = stride.checked_mul(height).ok_or_else(truncated)?;
+    let data = bytes
+        .get(data_offset..)
+        .and_then(|d| d.get(..size))
+        .ok_or_else(truncated)?;
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = if top_down { y } else { height - 1 - y };
+        let row = &data[row * stride..row * stride + width * bytes_per_pixel];
+        for p in row.chunks(bytes_per_pixel) {
+            let value = match p {
+                [b, g, r] => u32::from_le_bytes([*b, *g, *r, 0]),
+                _ => u32::from_le_bytes([p[0], p[1], p[2], p[3]]),
+            };
+            pixels.push(Color {
+                r: channel(value, masks[0]),
+                g: channel(value, masks[1]),
+                b: channel(value, masks[2]),
+                a: if masks[3] == 0 {
+                    255
+                } else {
+                    channel(value, masks[3])
+                },
+            });
+        }
+    }
+
+    Ok(Image::new(width, height, pixels))
+}
+
+// Extracts the bits under `mask`, scaled to 8 bits
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let bits = mask.count_ones();
+    let v = (value & mask) >> mask.trailing_zeros();
+    let max = (1u64 << bits) - 1;
+    ((v as u64 * 255 + max / 2) / max) as u8
+}
@@ -0,0 +1,526 @@
+//! zlib streams (RFC 1950) holding DEFLATE data (RFC 1951), as used by PNG.
+
+use super::ImageError;
+
+// Base values and extra bits of length codes 257..=285 and distance codes 0..=29
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// Order in which code length code lengths are stored in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// How many earlier positions with the same hash are tried for a match
+const MAX_CHAIN: usize = 64;
+
+/// Compresses `data` into a zlib stream.
+///
+/// Matches are found with hash chains over the 32 KiB window and written as a
+/// single block with the fixed Huffman codes.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // Deflate with a 32 KiB window, default compression level
+    writer.bytes.extend([0x78, 0x9C]);
+
+    // Final block, fixed Huffman codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    const HASH_SIZE: usize = 1 << 15;
+    let hash = |i: usize| {
+        let v = (data[i] as usize) << 16 | (data[i + 1] as usize) << 8 | data[i + 2] as usize;
+        (v.wrapping_mul(2654435761) >> 7) & (HASH_SIZE - 1)
+    };
+    // Most recent position of each hash, and the previous one with the same hash
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut previous = vec![usize::MAX; data.len()];
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            let max = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[h];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let length = data[candidate..candidate + max]
+                    .iter()
+                    .zip(&data[i..i + max])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, i - candidate);
+                    if length == max {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        let (length, distance) = best;
+        let step = if length >= MIN_MATCH {
+            writer.write_length(length);
+            writer.write_distance(distance);
+            length
+        } else {
+            writer.write_literal(data[i] as u16);
+            1
+        };
+
+        // Index every position covered, so later data can refer back to it
+        let end = (i + step).min((data.len() + 1).saturating_sub(MIN_MATCH));
+        for (j, link) in previous.iter_mut().enumerate().take(end).skip(i) {
+            let h = hash(j);
+            *link = head[h];
+            head[h] = j;
+        }
+        i += step;
+    }
+    writer.write_literal(256);
+
+    let mut out = writer.finish();
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// Decompresses a zlib stream, failing if it would produce more than `limit` bytes.
+pub fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, ImageError> {
+    let invalid = |what: &str| ImageError::Invalid(format!("zlib stream: {what}"));
+
+    let [cmf, flg, ..] = *data else {
+        return Err(invalid("missing header"));
+    };
+    if cmf & 0x0F != 8 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Err(invalid("bad header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(ImageError::Unsupported("zlib preset dictionary".into()));
+    }
+
+    let mut reader = BitReader {
+        data: &data[2..],
+        pos: 0,
+        bit: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let len = reader.bits(16)? as usize;
+                let nlen = reader.bits(16)? as usize;
+                if len != !nlen & 0xFFFF {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                if out.len() + len > limit {
+                    return Err(invalid("more data than expected"));
+                }
+                for _ in 0..len {
+                    out.push(reader.bits(8)? as u8);
+                }
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut reader, &mut out, &literals, &distances, limit)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances, limit)?;
+            }
+            _ => return Err(invalid("reserved block type")),
+        }
+        if last {
+            break;
+        }
+    }
+
+    reader.align();
+    let checksum = reader
+        .data
+        .get(reader.pos..reader.pos + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    if checksum != Some(adler32(&out)) {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(out)
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+    limit: usize,
+) -> Result<(), ImageError> {
+    let invalid = |what: &str| ImageError::Invalid(format!("deflate data: {what}"));
+
+    loop {
+        let symbol = reader.decode(literals)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let code = symbol - 257;
+                let length = LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code])? as usize;
+                let code = reader.decode(distances)? as usize;
+                if code >= DISTANCE_BASE.len() {
+                    return Err(invalid("bad distance code"));
+                }
+                let distance =
+                    DISTANCE_BASE[code] as usize + reader.bits(DISTANCE_EXTRA[code])? as usize;
+                if distance > out.len() {
+                    return Err(invalid("distance before start of data"));
+                }
+                // Copy byte by byte, the match may overlap what it produces
+                let start = out.len() - distance;
+                for k in 0..length {
+                    out.push(out[start + k]);
+                }
+            }
+            _ => return Err(invalid("bad length code")),
+        }
+        if out.len() > limit {
+            return Err(invalid("more data than expected"));
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), ImageError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    // Literal/length and distance code lengths are run-length coded together
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = reader.decode(&code_length_code)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| ImageError::Invalid("deflate data: repeat of nothing".into()))?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() != literal_count + distance_count {
+        return Err(ImageError::Invalid(
+            "deflate data: code lengths overflow".into(),
+        ));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+// Canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    // Number of codes of each length
+    counts: [u16; 16],
+    // Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..16 {
+            for (symbol, &l) in lengths.iter().enumerate() {
+                if l as usize == length {
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+        Huffman { counts, symbols }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+
+impl BitReader<'_> {
+    // Reads `count` bits, least significant first
+    fn bits(&mut self, count: u8) -> Result<u32, ImageError> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos).ok_or_else(super::truncated)?;
+            value |= (((byte >> self.bit) & 1) as u32) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> Result<u16, ImageError> {
+        // Codes of each length follow the last code of the previous length
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for length in 1..16 {
+            code |= self.bits(1)? as i32;
+            let count = huffman.counts[length] as i32;
+            if code - first < count {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(ImageError::Invalid("deflate data: bad Huffman code".into()))
+    }
+}
+
+/// Packs bits into bytes from the least significant bit, as both DEFLATE and
+/// GIF store them.
+#[derive(Default)]
+pub(super) struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    /// Writes the low `count` bits of `value`, least significant first.
+    pub(super) fn write(&mut self, value: u32, count: u8) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit
+    fn write_code(&mut self, code: u32, length: u8) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write(reversed, length);
+    }
+
+    fn write_literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_length(&mut self, length: usize) {
+        let code = LENGTH_BASE
+            .iter()
+            .rposition(|&b| b as usize <= length)
+            .unwrap();
+        self.write_literal(257 + code as u16);
+        let extra = length - LENGTH_BASE[code] as usize;
+        self.write(extra as u32, LENGTH_EXTRA[code]);
+    }
+
+    fn write_distance(&mut self, distance: usize) {
+        let code = DISTANCE_BASE
+            .iter()
+            .rposition(|&b| b as usize <= distance)
+            .unwrap();
+        self.write_code(code as u32, 5);
+        let extra = distance - DISTANCE_BASE[code] as usize;
+        self.write(extra as u32, DISTANCE_EXTRA[code]);
+    }
+
+    pub(super) fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums stay below 2^32 for this many bytes before they need reducing
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut n = 0;
+        while n < 256 {
+            let mut c = n as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[n] = c;
+            n += 1;
+        }
+        table
+    };
+
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc = TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xFFFF_FFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Made by zlib at level 9: a dynamic Huffman block, and a stored block
+    const DYNAMIC_ZLIB: &[u8] = &[
+        0x78, 0xda, 0x1d, 0x8a, 0x4b, 0x12, 0x80, 0x20, 0x0c, 0xc5, 0xae, 0xf2, 0xae, 0xc6, 0xa7,
+        0x42, 0x15, 0x5b, 0xa5, 0x20, 0xea, 0xe9, 0x75, 0xd8, 0x65, 0x92, 0xb4, 0x4c, 0x38, 0x3b,
+        0x87, 0x0d, 0xbe, 0xea, 0x10, 0x2c, 0x7a, 0x63, 0xed, 0xfb, 0x61, 0xd0, 0x8b, 0x2a, 0xda,
+        0x9f, 0x8b, 0x7b, 0x1f, 0x44, 0x4d, 0x18, 0x99, 0x0b, 0x4d, 0x15, 0x5c, 0x83, 0x15, 0xa2,
+        0x7f, 0x63, 0x99, 0xc6, 0xba, 0xc0, 0x49, 0x9c, 0xec, 0xb9, 0x46, 0x18, 0x4b, 0xb2, 0x0f,
+        0x62, 0x65, 0x22, 0x1f,
+    ];
+    const STORED_ZLIB: &[u8] = &[
+        0x78, 0x01, 0x01, 0x0c, 0x00, 0xf3, 0xff, 0x73, 0x74, 0x6f, 0x72, 0x65, 0x64, 0x20, 0x62,
+        0x6c, 0x6f, 0x63, 0x6b, 0x1f, 0x80, 0x04, 0xbd,
+    ];
+
+    // Deterministic bytes that hardly compress
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x1234_5678u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn compressed_data_round_trips() {
+        let mut mixed = noise(50_000);
+        mixed.extend(vec![7; 1000]);
+        mixed.extend_from_within(..40_000);
+        let cases = [
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcabcabcabcabcabcabcabc".to_vec(),
+            vec![0; 100_000],
+            noise(10_000),
+            mixed,
+        ];
+        for data in cases {
+            let compressed = compress(&data);
+            assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn repeats_are_compressed() {
+        assert!(compress(&[0; 100_000]).len() < 1000);
+        assert!(compress(&b"abc".repeat(1000)).len() < 100);
+    }
+
+    #[test]
+    fn streams_from_zlib_are_decompressed() {
+        let text = b"the quick brown fox jumps over the lazy dog while the cat sleeps in the sun and the bird sings";
+        assert_eq!(decompress(DYNAMIC_ZLIB, 1000).unwrap(), text);
+        assert_eq!(decompress(STORED_ZLIB, 1000).unwrap(), b"stored block");
+    }
+
+    #[test]
+    fn limits_and_corruption_are_errors() {
+        let data = noise(1000);
+        let compressed = compress(&data);
+        assert!(decompress(&compressed, 999).is_err());
+        assert!(decompress(STORED_ZLIB, 11).is_err());
+
+        let mut corrupted = compressed.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(decompress(&corrupted, 1000).is_err());
+        assert!(decompress(&compressed[..compressed.len() / 2], 1000).is_err());
+        assert!(decompress(&[0x78], 1000).is_err());
+        assert!(decompress(&[0x78, 0x9D], 1000).is_err());
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::deflate::BitWriter;
+use super::{Image, ImageError};
+use crate::engine::shader::Color;
+
+const MAX_COLORS: usize = 256;
+// Codes are at most 12 bits wide
+const MAX_CODES: u16 = 4096;
+
+/// Writes frames into an animated GIF that loops forever.
+///
+/// Each frame gets its own palette of up to 256 colors. Frames with few
+/// colors, such as wireframes, keep them exactly; others are reduced by median
+/// cut and optionally Floyd–Steinberg dithered. Alpha is ignored.
+pub struct GifWriter<W: Write> {
+    writer: W,
+    width: usize,
+    height: usize,
+    dither: bool,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Writes the header. GIF sizes are limited to 65535 pixels per side.
+    pub fn new(
+        mut writer: W,
+        width: usize,
+        height: usize,
+        dither: bool,
+    ) -> Result<Self, ImageError> {
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(ImageError::Unsupported(format!("{width}x{height} GIF")));
+        }
+
+        writer.write_all(b"GIF89a")?;
+        writer.write_all(&(width as u16).to_le_bytes())?;
+        writer.write_all(&(height as u16).to_le_bytes())?;
+        // No global color table, background color 0, square pixels
+        writer.write_all(&[0, 0, 0])?;
+
+        // Application extension that makes viewers loop the animation
+        writer.write_all(&[0x21, 0xFF, 11])?;
+        writer.write_all(b"NETSCAPE2.0")?;
+        writer.write_all(&[3, 1, 0, 0, 0])?;
+
+        Ok(GifWriter {
+            writer,
+            width,
+            height,
+            dither,
+        })
+    }
+
+    /// Adds a frame shown for `delay` hundredths of a second.
+    pub fn write_frame(&mut self, image: &Image, delay: u16) -> Result<(), ImageError> {
+        if image.width != self.width || image.height != self.height {
+            return Err(ImageError::Invalid(format!(
+                "{}x{} frame in a {}x{} GIF",
+                image.width, image.height, self.width, self.height
+            )));
+        }
+
+        let (palette, indices) = quantize(image, self.dither);
+
+        // Graphic control extension: no disposal, no transparency
+        self.writer.write_all(&[0x21, 0xF9, 4, 0])?;
+        self.writer.write_all(&delay.to_le_bytes())?;
+        self.writer.write_all(&[0, 0])?;
+
+        // Image descriptor covering the whole screen, with a local color
+        // table of 256 entries
+        self.writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.writer.write_all(&(self.width as u16).to_le_bytes())?;
+        self.writer.write_all(&(self.height as u16).to_le_bytes())?;
+        self.writer.write_all(&[0x80 | 7])?;
+        for i in 0..MAX_COLORS {
+            let c = palette.get(i).copied().unwrap_or(Color::TRANSPARENT);
+            self.writer.write_all(&[c.r, c.g, c.b])?;
+        }
+
+        // LZW data in sub-blocks of at most 255 bytes
+        self.writer.write_all(&[8])?;
+        for block in lzw(&indices).chunks(255) {
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+        self.writer.write_all(&[0])?;
+        Ok(())
+    }
+
+    /// Writes the trailer and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, ImageError> {
+        self.writer.write_all(&[0x3B])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// Picks a palette for the image and the palette index of every pixel
+fn quantize(image: &Image, dither: bool) -> (Vec<Color>, Vec<u8>) {
+    let opaque = |c: &Color| [c.r, c.g, c.b];
+
+    // Few enough colors to keep them all
+    let mut exact: HashMap<[u8; 3], u8> = HashMap::new();
+    for c in &image.pixels {
+        let next = exact.len();
+        exact.entry(opaque(c)).or_insert(next as u8);
+        if exact.len() > MAX_COLORS {
+            break;
+        }
+    }
+    if exact.len() <= MAX_COLORS {
+        let mut palette = vec![Color::TRANSPARENT; exact.len()];
+        for (&[r, g, b], &i) in &exact {
+            palette[i as usize] = Color { r, g, b, a: 255 };
+        }
+        let indices = image.pixels.iter().map(|c| exact[&opaque(c)]).collect();
+        return (palette, indices);
+    }
+
+    let palette = median_cut(image);
+
+    // Nearest palette entry for each 5-bit-per-channel color, found on demand
+    let mut nearest = vec![u8::MAX; 1 << 15];
+    let mut known = vec![false; 1 << 15];
+    let mut lookup = |rgb: [i32; 3]| {
+        let [r, g, b] = rgb.map(|v| v.clamp(0, 255) as usize);
+        let key = (r >> 3) << 10 | (g >> 3) << 5 | b >> 3;
+        if !known[key] {
+            let center = [r >> 3, g >> 3, b >> 3].map(|v| (v << 3 | 4) as i32);
+            nearest[key] = closest(&palette, center);
+            known[key] = true;
+        }
+        nearest[key]
+    };
+
+    let (width, height) = (image.width, image.height);
+    let mut indices = Vec::with_capacity(width * height);
+    if !dither {
+        for c in &image.pixels {
+            indices.push(lookup([c.r as i32, c.g as i32, c.b as i32]));
+        }
+        return (palette, indices);
+    }
+
+    // Floyd–Steinberg: the error of each pixel is spread to the right and to
+    // the row below, in sixteenths
+    let mut error = vec![[0i32; 3]; width + 2];
+    let mut next_error = vec![[0i32; 3]; width + 2];
+    for y in 0..height {
+        for x in 0..width {
+            let c = image.pixels[y * width + x];
+            let e = error[x + 1];
+            let want = [
+                c.r as i32 + e[0] / 16,
+                c.g as i32 + e[1] / 16,
+                c.b as i32 + e[2] / 16,
+            ];
+            let index = lookup(want);
+            indices.push(index);
+
+            let got = palette[index as usize];
+            let got = [got.r as i32, got.g as i32, got.b as i32];
+            for ch in 0..3 {
+                let diff = want[ch].clamp(0, 255) - got[ch];
+                error[x + 2][ch] += diff * 7;
+                next_error[x][ch] += diff * 3;
+                next_error[x + 1][ch] += diff * 5;
+                next_error[x + 2][ch] += diff;
+            }
+        }
+        std::mem::swap(&mut error, &mut next_error);
+        next_error.fill([0; 3]);
+    }
+    (palette, indices)
+}
+
+// Splits the colors of the image into boxes holding about equal numbers of
+// pixels, splitting across the widest channel, and averages each box
+fn median_cut(image: &Image) -> Vec<Color> {
+    // Pixel count and channel sums per 5-bit-per-channel color
+    let mut histogram: HashMap<u16, (u32, [u64; 3])> = HashMap::new();
+    for c in &image.pixels {
+        let key = (c.r as u16 >> 3) << 10 | (c.g as u16 >> 3) << 5 | c.b as u16 >> 3;
+        let entry = histogram.entry(key).or_insert((0, [0; 3]));
+        entry.0 += 1;
+        entry.1[0] += c.r as u64;
+        entry.1[1] += c.g as u64;
+        entry.1[2] += c.b as u64;
+    }
+    let channel = |key: u16, ch: usize| (key >> (10 - ch * 5)) & 0x1F;
+
+    // Sorted so the palette does not depend on hash order
+    let mut colors: Vec<(u16, (u32, [u64; 3]))> = histogram.into_iter().collect();
+    colors.sort_unstable_by_key(|(key, _)| *key);
+
+    let mut boxes = vec![colors];
+    while boxes.len() < MAX_COLORS {
+        // Box with the widest channel range, weighted by pixel count
+        let widest = |entries: &[(u16, (u32, [u64; 3]))]| {
+            (0..3)
+                .map(|ch| {
+                    let values = entries.iter().map(|(k, _)| channel(*k, ch));
+                    let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                    (range, ch)
+                })
+                .max()
+                .unwrap_or((0, 0))
+        };
+        let Some((index, ch)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (range, ch) = widest(b);
+                let pixels: u64 = b.iter().map(|(_, (n, _))| *n as u64).sum();
+                (range as u64 * pixels, i, ch)
+            })
+            .max()
+            .map(|(_, i, ch)| (i, ch))
+        else {
+            break;
+        };
+
+        let mut entries = std::mem::take(&mut boxes[index]);
+        entries.sort_by_key(|(k, _)| channel(*k, ch));
+        let total: u64 = entries.iter().map(|(_, (n, _))| *n as u64).sum();
+        let mut count = 0;
+        let mut split = entries.len() - 1;
+        for (i, (_, (n, _))) in entries.iter().enumerate() {
+            count += *n as u64;
+            if count * 2 >= total {
+                split = (i + 1).min(entries.len() - 1);
+                break;
+            }
+        }
+        boxes[index] = entries.split_off(split);
+        boxes.push(entries);
+    }
+
+    boxes
+        .iter()
+        .map(|entries| {
+            let mut n = 0u64;
+            let mut sum = [0u64; 3];
+            for (_, (count, s)) in entries {
+                n += *count as u64;
+                for ch in 0..3 {
+                    sum[ch] += s[ch];
+                }
+            }
+            let n = n.max(1);
+            Color {
+                r: ((sum[0] + n / 2) / n) as u8,
+                g: ((sum[1] + n / 2) / n) as u8,
+                b: ((sum[2] + n / 2) / n) as u8,
+                a: 255,
+            }
+        })
+        .collect()
+}
+
+fn closest(palette: &[Color], rgb: [i32; 3]) -> u8 {
+    let distance = |c: &Color| {
+        let d = [
+            c.r as i32 - rgb[0],
+            c.g as i32 - rgb[1],
+            c.b as i32 - rgb[2],
+        ];
+        d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+    };
+    (0..palette.len())
+        .min_by_key(|&i| distance(&palette[i]))
+        .unwrap_or(0) as u8
+}
+
+// Variable-width LZW over 8-bit indices, restarting when the table is full
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+
+    let mut writer = BitWriter::default();
+    let mut size = 9;
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = END + 1;
+    writer.write(CLEAR as u32, size);
+
+    let Some((&first, rest)) = indices.split_first() else {
+        writer.write(END as u32, size);
+        return writer.finish();
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        writer.write(prefix as u32, size);
+        if next < MAX_CODES {
+            table.insert((prefix, index), next);
+            next += 1;
+            // The decoder adds entries one code later, so it widens its codes
+            // after reading the code that filled the current width
+            if next > 1 << size && size < 12 {
+                size += 1;
+            }
+        } else {
+            writer.write(CLEAR as u32, size);
+            table.clear();
+            next = END + 1;
+            size = 9;
+        }
+        prefix = index as u16;
+    }
+    writer.write(prefix as u32, size);
+    if next + 1 > 1 << size && size < 12 {
+        size += 1;
+    }
+    writer.write(END as u32, size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::tests::gradient;
+
+    // Reads LZW codes the way GIF decoders do
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let initial = || -> Vec<Vec<u8>> { (0..=257).map(|i| vec![i as u8]).collect() };
+        let mut table = initial();
+        let mut size = 9;
+        let mut bit = 0;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        loop {
+            let mut code = 0;
+            for i in 0..size {
+                code |= ((data[bit / 8] >> (bit % 8)) as usize & 1) << i;
+                bit += 1;
+            }
+            match code {
+                256 => {
+                    table = initial();
+                    size = 9;
+                    previous = None;
+                    continue;
+                }
+                257 => return out,
+                _ => {}
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(p)) => [p.as_slice(), &p[..1]].concat(),
+                (None, None) => panic!("code {code} before any entry"),
+            };
+            if let Some(mut p) = previous.filter(|_| table.len() < MAX_CODES as usize) {
+                p.push(entry[0]);
+                table.push(p);
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            out.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x9E37_79B9u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    // Palette colors of the pixels of the single frame of a GIF
+    fn decode_frame(gif: &[u8]) -> Vec<Color> {
+        // Header, loop extension and graphic control extension
+        let descriptor = 13 + 19 + 8;
+        assert_eq!(gif[descriptor], 0x2C);
+        let palette: Vec<Color> = gif[descriptor + 10..descriptor + 10 + 768]
+            .chunks_exact(3)
+            .map(|c| Color {
+                r: c[0],
+                g: c[1],
+                b: c[2],
+                a: 255,
+            })
+            .collect();
+        let mut pos = descriptor + 10 + 768;
+        assert_eq!(gif[pos], 8);
+        pos += 1;
+        let mut data = Vec::new();
+        while gif[pos] != 0 {
+            let len = gif[pos] as usize;
+            data.extend_from_slice(&gif[pos + 1..pos + 1 + len]);
+            pos += 1 + len;
+        }
+        assert_eq!(&gif[pos + 1..], [0x3B]);
+        unlzw(&data)
+            .into_iter()
+            .map(|i| palette[i as usize])
+            .collect()
+    }
+
+    #[test]
+    fn empty_input_is_clear_then_end() {
+        // Codes 256 and 257 at 9 bits, least significant bit first
+        assert_eq!(lzw(&[]), [0x00, 0x03, 0x02]);
+    }
+
+    #[test]
+    fn lzw_codes_round_trip() {
+        let cases = [
+            vec![42],
+            b"TOBEORNOTTOBEORTOBEORNOT".to_vec(),
+            vec![0; 100_000],
+            (0..=255).collect(),
+            // Enough distinct strings to fill the table several times
+            noise(50_000),
+            noise(3000).repeat(4),
+        ];
+        for indices in cases {
+            assert_eq!(unlzw(&lzw(&indices)), indices);
+        }
+    }
+
+    #[test]
+    fn frames_keep_images_with_few_colors_exactly() {
+        let image = gradient(5, 4, false);
+        let mut writer = GifWriter::new(Vec::new(), 5, 4, false).unwrap();
+        writer.write_frame(&image, 3).unwrap();
+        let gif = writer.finish().unwrap();
+
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(&gif[6..10], [5, 0, 4, 0]);
+        assert_eq!(&gif[13 + 19 + 4..13 + 19 + 6], [3, 0]);
+        assert_eq!(decode_frame(&gif), image.pixels);
+    }
+
+    #[test]
+    fn frames_with_many_colors_stay_close() {
+        // Smooth red and green ramps, 5917 colors
+        let pixels = (0..97 * 61)
+            .map(|i| Color {
+                r: (i % 97 * 255 / 96) as u8,
+                g: (i / 97 * 255 / 60) as u8,
+                b: 128,
+                a: 255,
+            })
+            .collect();
+        let image = Image::new(97, 61, pixels);
+        for dither in [false, true] {
+            let mut writer = GifWriter::new(Vec::new(), 97, 61, dither).unwrap();
+            writer.write_frame(&image, 2).unwrap();
+            let pixels = decode_frame(&writer.finish().unwrap());
+
+            let error: u32 = pixels
+                .iter()
+                .zip(&image.pixels)
+                .map(|(a, b)| {
+                    a.r.abs_diff(b.r) as u32 + a.g.abs_diff(b.g) as u32 + a.b.abs_diff(b.b) as u32
+                })
+                .sum();
+            let mean = error as f32 / (image.pixels.len() * 3) as f32;
+            assert!(mean < 4.0, "mean error {mean} with dither {dither}");
+        }
+    }
+
+    #[test]
+    fn sizes_must_fit_and_match() {
+        assert!(GifWriter::new(Vec::new(), 65_536, 1, false).is_err());
+        let mut writer = GifWriter::new(Vec::new(), 4, 4, false).unwrap();
+        assert!(writer.write_frame(&gradient(4, 5, false), 1).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+mod bmp;
+mod deflate;
+mod gif;
+mod png;
+mod ppm;
+mod tga;
+mod y4m;
+
+pub use gif::GifWriter;
+pub use y4m::Y4mWriter;
+
+use std::fmt;
+use std::path::Path;
+
+use crate::engine::pixel::PixelFormat;
+use crate::engine::shader::Color;
+
+/// File formats images can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Binary PPM (`P6`). Has no alpha channel.
+    Ppm,
+    /// Plain text PPM (`P3`). Has no alpha channel.
+    PpmAscii,
+    /// Uncompressed 32-bit TGA.
+    Tga,
+    /// Run-length encoded 32-bit TGA.
+    TgaRle,
+    /// 24-bit BMP. Has no alpha channel.
+    Bmp24,
+    /// 32-bit BMP with an alpha channel.
+    Bmp32,
+    /// Compressed 8-bit RGBA PNG.
+    Png,
+}
+
+impl ImageFormat {
+    /// Picks a format from a file extension: binary PPM, RLE TGA, PNG, and
+    /// 32-bit BMP if `alpha` is needed or 24-bit otherwise.
+    pub fn from_path(path: &Path, alpha: bool) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "tga" => Some(ImageFormat::TgaRle),
+            "bmp" if alpha => Some(ImageFormat::Bmp32),
+            "bmp" => Some(ImageFormat::Bmp24),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    /// The usual file extension, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Ppm | ImageFormat::PpmAscii => "ppm",
+            ImageFormat::Tga | ImageFormat::TgaRle => "tga",
+            ImageFormat::Bmp24 | ImageFormat::Bmp32 => "bmp",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(std::io::Error),
+    /// The data is a kind of image this module does not handle.
+    Unsupported(String),
+    /// The data is damaged or not an image at all.
+    Invalid(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(err) => write!(f, "I/O error: {err}"),
+            ImageError::Unsupported(what) => write!(f, "unsupported image: {what}"),
+            ImageError::Invalid(what) => write!(f, "invalid image: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ImageError {
+    fn from(err: std::io::Error) -> Self {
+        ImageError::Io(err)
+    }
+}
+
+/// Pixels in rows from the top, with straight alpha.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixel count does not match size"
+        );
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Unpacks a display buffer such as `View::buffer`.
+    pub fn from_buffer(width: usize, height: usize, buffer: &[u32], format: PixelFormat) -> Self {
+        let pixels = buffer.iter().map(|&p| format.unpack(p)).collect();
+        Self::new(width, height, pixels)
+    }
+
+    /// Packs the pixels for a display buffer.
+    pub fn to_buffer(&self, format: PixelFormat) -> Vec<u32> {
+        self.pixels.iter().map(|&c| format.pack(c)).collect()
+    }
+
+    /// Whether any pixel is not fully opaque.
+    pub fn has_alpha(&self) -> bool {
+        self.pixels.iter().any(|c| c.a < 255)
+    }
+
+    /// Reads an image, telling PPM, BMP and PNG apart by their signature.
+    /// Anything else is read as TGA, which has none.
+    pub fn load(path: impl AsRef<Path>) -> Result<Image, ImageError> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+        match bytes {
+            [b'P', b'3' | b'6', ..] => ppm::decode(bytes),
+            [b'P', ..] => Err(ImageError::Unsupported("PPM variant".into())),
+            [b'B', b'M', ..] => bmp::decode(bytes),
+            _ if png::is_png(bytes) => png::decode(bytes),
+            _ => tga::decode(bytes),
+        }
+    }
+
+    /// Writes the image in the format matching the extension of `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path, self.has_alpha()).ok_or_else(|| {
+            ImageError::Unsupported(format!("file extension of {}", path.display()))
+        })?;
+        self.save_as(path, format)
+    }
+
+    pub fn save_as(&self, path: impl AsRef<Path>, format: ImageFormat) -> Result<(), ImageError> {
+        std::fs::write(path, self.encode(format)?)?;
+        Ok(())
+    }
+
+    /// Fails if the format cannot store an image of this size.
+    pub fn encode(&self, format: ImageFormat) -> Result<Vec<u8>, ImageError> {
+        match format {
+            ImageFormat::Ppm => Ok(ppm::encode(self, false)),
+            ImageFormat::PpmAscii => Ok(ppm::encode(self, true)),
+            ImageFormat::Tga => tga::encode(self, false),
+            ImageFormat::TgaRle => tga::encode(self, true),
+            ImageFormat::Bmp24 => Ok(bmp::encode(self, false)),
+            ImageFormat::Bmp32 => Ok(bmp::encode(self, true)),
+            ImageFormat::Png => Ok(png::encode(self)),
+        }
+    }
+}
+
+// Little-endian readers that fail on truncated data
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, ImageError> {
+    match bytes.get(offset..offset + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => Err(truncated()),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ImageError> {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(truncated()),
+    }
+}
+
+fn truncated() -> ImageError {
+    ImageError::Invalid("unexpected end of data".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Odd sizes, so BMP rows need padding, with every channel varying
+    pub(super) fn gradient(width: usize, height: usize, alpha: bool) -> Image {
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                Color {
+                    r: (x * 255 / width) as u8,
+                    g: (y * 37 % 256) as u8,
+                    b: ((x * 7 + y * 13) % 256) as u8,
+                    a: if alpha {
+                        ((x + y) * 16 % 256) as u8
+                    } else {
+                        255
+                    },
+                }
+            })
+            .collect();
+        Image::new(width, height, pixels)
+    }
+
+    fn round_trip(image: &Image, format: ImageFormat) -> Image {
+        Image::decode(&image.encode(format).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn opaque_formats_round_trip() {
+        let image = gradient(7, 5, false);
+        for format in [
+            ImageFormat::Ppm,
+            ImageFormat::PpmAscii,
+            ImageFormat::Bmp24,
+            ImageFormat::Tga,
+            ImageFormat::TgaRle,
+            ImageFormat::Bmp32,
+        ] {
+            let decoded = round_trip(&image, format);
+            assert_eq!((decoded.width, decoded.height), (7, 5), "{format:?}");
+            assert_eq!(decoded.pixels, image.pixels, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn alpha_formats_keep_alpha() {
+        let image = gradient(6, 3, true);
+        for format in [ImageFormat::Tga, ImageFormat::TgaRle, ImageFormat::Bmp32] {
+            assert_eq!(
+                round_trip(&image, format).pixels,
+                image.pixels,
+                "{format:?}"
+            );
+        }
+        // Formats without alpha come back opaque
+        let decoded = round_trip(&image, ImageFormat::Bmp24);
+        assert!(!decoded.has_alpha());
+    }
+
+    #[test]
+    fn rle_compresses_runs_across_packet_limits() {
+        let mut pixels = vec![Color::WHITE; 300];
+        pixels[150] = Color::TRANSPARENT;
+        let image = Image::new(300, 1, pixels);
+        let encoded = image.encode(ImageFormat::TgaRle).unwrap();
+        assert!(encoded.len() < 50, "{} bytes", encoded.len());
+        assert_eq!(Image::decode(&encoded).unwrap().pixels, image.pixels);
+    }
+
+    #[test]
+    fn plain_ppm_with_comments_and_small_maximum() {
+        let bytes = b"P3\n# two pixels\n2 1 # size\n15\n15 0 0  0 15 7\n";
+        let image = Image::decode(bytes).unwrap();
+        assert_eq!(
+            image.pixels,
+            [
+                Color {
+                    r: 255,
+                    g: 0,
+                    b: 0,
+                    a: 255
+                },
+                Color {
+                    r: 0,
+                    g: 255,
+                    b: 119,
+                    a: 255
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sixteen_bit_ppm_is_scaled_down() {
+        let mut bytes = b"P6 1 1 65535\n".to_vec();
+        bytes.extend([0xFF, 0xFF, 0x80, 0x00, 0x00, 0x00]);
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!(
+            image.pixels[0],
+            Color {
+                r: 255,
+                g: 128,
+                b: 0,
+                a: 255
+            }
+        );
+    }
+
+    #[test]
+    fn bottom_up_tga_is_flipped() {
+        // 24-bit, 1x2, bottom row first: blue then red
+        let mut bytes = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2, 0, 24, 0];
+        bytes.extend([255, 0, 0, 0, 0, 255]);
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!((image.pixels[0].r, image.pixels[0].b), (255, 0));
+        assert_eq!((image.pixels[1].r, image.pixels[1].b), (0, 255));
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let image = gradient(5, 4, true);
+        for format in [ImageFormat::Ppm, ImageFormat::TgaRle, ImageFormat::Bmp32] {
+            let encoded = image.encode(format).unwrap();
+            for len in [3, encoded.len() / 2, encoded.len() - 1] {
+                assert!(Image::decode(&encoded[..len]).is_err(), "{format:?} {len}");
+            }
+        }
+        // Plain PPM only ends in a newline that is not needed
+        let encoded = image.encode(ImageFormat::PpmAscii).unwrap();
+        assert!(Image::decode(&encoded[..encoded.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn formats_follow_extensions() {
+        let format = |name: &str, alpha| ImageFormat::from_path(Path::new(name), alpha);
+        assert_eq!(format("a.PPM", false), Some(ImageFormat::Ppm));
+        assert_eq!(format("a.tga", false), Some(ImageFormat::TgaRle));
+        assert_eq!(format("a.bmp", true), Some(ImageFormat::Bmp32));
+        assert_eq!(format("a.bmp", false), Some(ImageFormat::Bmp24));
+        assert_eq!(format("a.jpg", false), None);
+        assert_eq!(format("a", false), None);
+    }
+}
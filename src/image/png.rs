@@ -0,0 +1,507 @@
+use super::deflate::{self, crc32};
+use super::{Image, ImageError, truncated};
+use crate::engine::shader::Color;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Color types
+const GRAYSCALE: u8 = 0;
+const TRUE_COLOR: u8 = 2;
+const INDEXED: u8 = 3;
+const GRAYSCALE_ALPHA: u8 = 4;
+const TRUE_COLOR_ALPHA: u8 = 6;
+
+// Start and spacing of the pixels in each of the seven Adam7 interlacing passes
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(&SIGNATURE)
+}
+
+/// Writes 8-bit RGBA, choosing the filter of each row that is likely to
+/// compress best.
+pub fn encode(image: &Image) -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((image.width as u32).to_be_bytes());
+    header.extend((image.height as u32).to_be_bytes());
+    // Bit depth, color type, compression, filter method, no interlacing
+    header.extend([8, TRUE_COLOR_ALPHA, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header);
+
+    let stride = image.width * 4;
+    let mut filtered = Vec::with_capacity((stride + 1) * image.height);
+    let mut previous = vec![0; stride];
+    let mut candidate = vec![0; stride];
+    for row in image.pixels.chunks(image.width.max(1)) {
+        let raw: Vec<u8> = row.iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect();
+
+        // Smallest sum of the filtered bytes taken as signed values
+        let mut best = (u64::MAX, 0, Vec::new());
+        for filter in 0..5 {
+            apply_filter(filter, &raw, &previous, 4, &mut candidate);
+            let cost = candidate
+                .iter()
+                .map(|&b| (b as i8).unsigned_abs() as u64)
+                .sum();
+            if cost < best.0 {
+                best = (cost, filter, candidate.clone());
+            }
+        }
+
+        filtered.push(best.1);
+        filtered.extend(best.2);
+        previous = raw;
+    }
+
+    write_chunk(&mut out, b"IDAT", &deflate::compress(&filtered));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+    let invalid = |what: &str| ImageError::Invalid(format!("PNG: {what}"));
+
+    let mut header = None;
+    let mut palette: Vec<Color> = Vec::new();
+    let mut transparent: Option<Vec<u8>> = None;
+    let mut data = Vec::new();
+
+    // Chunks: length, type, data, CRC of type and data
+    let mut pos = SIGNATURE.len();
+    loop {
+        let length = read_u32_be(bytes, pos)? as usize;
+        let body = bytes.get(pos + 4..pos + 8 + length).ok_or_else(truncated)?;
+        let crc = read_u32_be(bytes, pos + 8 + length)?;
+        if crc32(body) != crc {
+            return Err(invalid("chunk checksum mismatch"));
+        }
+        let (kind, chunk) = body.split_at(4);
+        pos += 12 + length;
+
+        match kind {
+            b"IHDR" => header = Some(Header::parse(chunk)?),
+            b"PLTE" => {
+                palette = chunk
+                    .chunks_exact(3)
+                    .map(|c| Color {
+                        r: c[0],
+                        g: c[1],
+                        b: c[2],
+                        a: 255,
+                    })
+                    .collect();
+            }
+            b"tRNS" => transparent = Some(chunk.to_vec()),
+            b"IDAT" => data.extend_from_slice(chunk),
+            b"IEND" => break,
+            // Unknown critical chunks change how the image is read
+            _ if kind[0].is_ascii_uppercase() => {
+                let name = String::from_utf8_lossy(kind);
+                return Err(ImageError::Unsupported(format!("PNG chunk {name}")));
+            }
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or_else(|| invalid("missing header"))?;
+    let (width, height) = (header.width, header.height);
+
+    // Transparency applies per palette entry, or to one exact gray or RGB value
+    if let (INDEXED, Some(alphas)) = (header.color_type, &transparent) {
+        for (entry, &alpha) in palette.iter_mut().zip(alphas) {
+            entry.a = alpha;
+        }
+    }
+    let key: Option<Vec<u16>> = match (header.color_type, &transparent) {
+        (GRAYSCALE | TRUE_COLOR, Some(t)) => Some(
+            t.chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    // Filter type byte plus packed samples for every row of every pass
+    let passes: Vec<(usize, usize, usize, usize)> = if header.interlaced {
+        ADAM7.to_vec()
+    } else {
+        vec![(0, 0, 1, 1)]
+    };
+    let pass_size = |&(x0, y0, dx, dy): &(usize, usize, usize, usize)| {
+        let w = (width + dx - 1 - x0) / dx;
+        let h = (height + dy - 1 - y0) / dy;
+        (w, h)
+    };
+    let mut expected = 0usize;
+    for pass in &passes {
+        let (w, h) = pass_size(pass);
+        if w > 0 && h > 0 {
+            let size = header
+                .row_bytes(w)
+                .and_then(|r| (r + 1).checked_mul(h))
+                .ok_or_else(|| invalid("image too large"))?;
+            expected = expected.saturating_add(size);
+        }
+    }
+    let data = deflate::decompress(&data, expected)?;
+    if data.len() < expected {
+        return Err(truncated());
+    }
+
+    let bpp = header.bytes_per_pixel();
+    let mut pixels = vec![Color::TRANSPARENT; width * height];
+    let mut offset = 0;
+    for pass in &passes {
+        let (w, h) = pass_size(pass);
+        if w == 0 || h == 0 {
+            continue;
+        }
+        let (x0, y0, dx, dy) = *pass;
+        let stride = header.row_bytes(w).unwrap_or(0);
+
+        let mut previous = vec![0; stride];
+        let mut row = vec![0; stride];
+        for y in 0..h {
+            let filter = data[offset];
+            let line = &data[offset + 1..offset + 1 + stride];
+            offset += 1 + stride;
+            if filter > 4 {
+                return Err(invalid("bad filter type"));
+            }
+            undo_filter(filter, line, &previous, bpp, &mut row);
+
+            for x in 0..w {
+                let color = header.pixel(&row, x, &palette, key.as_deref());
+                pixels[(y0 + y * dy) * width + x0 + x * dx] = color;
+            }
+            std::mem::swap(&mut previous, &mut row);
+        }
+    }
+
+    Ok(Image::new(width, height, pixels))
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(chunk: &[u8]) -> Result<Header, ImageError> {
+        if chunk.len() < 13 {
+            return Err(truncated());
+        }
+        let header = Header {
+            width: read_u32_be(chunk, 0)? as usize,
+            height: read_u32_be(chunk, 4)? as usize,
+            depth: chunk[8],
+            color_type: chunk[9],
+            interlaced: chunk[12] == 1,
+        };
+
+        let valid_depth = match header.color_type {
+            GRAYSCALE => matches!(header.depth, 1 | 2 | 4 | 8 | 16),
+            INDEXED => matches!(header.depth, 1 | 2 | 4 | 8),
+            TRUE_COLOR | GRAYSCALE_ALPHA | TRUE_COLOR_ALPHA => matches!(header.depth, 8 | 16),
+            _ => false,
+        };
+        if !valid_depth || chunk[10] != 0 || chunk[11] != 0 || chunk[12] > 1 {
+            return Err(ImageError::Unsupported(format!(
+                "PNG color type {} with bit depth {}",
+                header.color_type, header.depth
+            )));
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            TRUE_COLOR => 3,
+            GRAYSCALE_ALPHA => 2,
+            TRUE_COLOR_ALPHA => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.depth as usize
+    }
+
+    // Distance to the corresponding byte of the previous pixel, for filtering
+    fn bytes_per_pixel(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    fn row_bytes(&self, width: usize) -> Option<usize> {
+        Some(width.checked_mul(self.bits_per_pixel())?.div_ceil(8))
+    }
+
+    // Sample `index` of an unfiltered row, at full precision
+    fn sample(&self, row: &[u8], index: usize) -> u16 {
+        match self.depth {
+            16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+            8 => row[index] as u16,
+            depth => {
+                // Packed from the most significant bit
+                let bit = index * depth as usize;
+                let shift = 8 - depth as usize - bit % 8;
+                ((row[bit / 8] >> shift) & ((1 << depth) - 1)) as u16
+            }
+        }
+    }
+
+    fn pixel(&self, row: &[u8], x: usize, palette: &[Color], key: Option<&[u16]>) -> Color {
+        let channels = self.channels();
+        let samples: Vec<u16> = (0..channels)
+            .map(|c| self.sample(row, x * channels + c))
+            .collect();
+
+        let max = (1u32 << self.depth) - 1;
+        let scale = |v: u16| ((v as u32 * 255 + max / 2) / max) as u8;
+        let opaque = |matches_key: bool| if matches_key { 0 } else { 255 };
+
+        match self.color_type {
+            INDEXED => palette
+                .get(samples[0] as usize)
+                .copied()
+                .unwrap_or(Color::TRANSPARENT),
+            GRAYSCALE => {
+                let v = scale(samples[0]);
+                Color {
+                    r: v,
+                    g: v,
+                    b: v,
+                    a: opaque(key == Some(&samples[..])),
+                }
+            }
+            GRAYSCALE_ALPHA => {
+                let v = scale(samples[0]);
+                Color {
+                    r: v,
+                    g: v,
+                    b: v,
+                    a: scale(samples[1]),
+                }
+            }
+            TRUE_COLOR => Color {
+                r: scale(samples[0]),
+                g: scale(samples[1]),
+                b: scale(samples[2]),
+                a: opaque(key == Some(&samples[..])),
+            },
+            _ => Color {
+                r: scale(samples[0]),
+                g: scale(samples[1]),
+                b: scale(samples[2]),
+                a: scale(samples[3]),
+            },
+        }
+    }
+}
+
+fn apply_filter(filter: u8, raw: &[u8], previous: &[u8], bpp: usize, out: &mut [u8]) {
+    for i in 0..raw.len() {
+        let left = if i >= bpp { raw[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        out[i] = raw[i].wrapping_sub(predict(filter, left, up, up_left));
+    }
+}
+
+fn undo_filter(filter: u8, line: &[u8], previous: &[u8], bpp: usize, out: &mut [u8]) {
+    for i in 0..line.len() {
+        let left = if i >= bpp { out[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        out[i] = line[i].wrapping_add(predict(filter, left, up, up_left));
+    }
+}
+
+// Value the filter predicts from the neighbouring bytes: none, left, up,
+// their average, or the Paeth predictor
+fn predict(filter: u8, left: u8, up: u8, up_left: u8) -> u8 {
+    match filter {
+        0 => 0,
+        1 => left,
+        2 => up,
+        3 => ((left as u16 + up as u16) / 2) as u8,
+        _ => {
+            let (a, b, c) = (left as i16, up as i16, up_left as i16);
+            let p = a + b - c;
+            let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+            if pa <= pb && pa <= pc {
+                left
+            } else if pb <= pc {
+                up
+            } else {
+                up_left
+            }
+        }
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Result<u32, ImageError> {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(truncated()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::tests::gradient;
+
+    // Made with zlib: a 3x2 1-bit grayscale checkerboard, a 2x2 2-bit palette
+    // with a half transparent entry, a 2x1 16-bit RGB row using the Sub filter,
+    // and a 3x3 interlaced 8-bit grayscale image
+    const GRAY_1BIT: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0xb5,
+        0x0f, 0x5b, 0xb7, 0x00, 0x00, 0x00, 0x0c, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0x58,
+        0xc0, 0xe0, 0x00, 0x00, 0x02, 0x24, 0x00, 0xe1, 0x4d, 0x16, 0xe8, 0x5e, 0x00, 0x00, 0x00,
+        0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+    const PALETTE_2BIT: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x02, 0x03, 0x00, 0x00, 0x00, 0x0f,
+        0xd8, 0xe5, 0xb7, 0x00, 0x00, 0x00, 0x09, 0x50, 0x4c, 0x54, 0x45, 0xff, 0x00, 0x00, 0x00,
+        0xff, 0x00, 0x00, 0x00, 0xff, 0x2d, 0x4a, 0xcd, 0x8a, 0x00, 0x00, 0x00, 0x02, 0x74, 0x52,
+        0x4e, 0x53, 0xff, 0x80, 0x08, 0x0f, 0xb3, 0x6a, 0x00, 0x00, 0x00, 0x0c, 0x49, 0x44, 0x41,
+        0x54, 0x78, 0xda, 0x63, 0x90, 0x60, 0x68, 0x00, 0x00, 0x00, 0xcc, 0x00, 0x99, 0x0c, 0x9b,
+        0xba, 0x8a, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+    const RGB_16BIT_SUB: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x10, 0x02, 0x00, 0x00, 0x00, 0x2b,
+        0xd0, 0x34, 0x9e, 0x00, 0x00, 0x00, 0x15, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xfc,
+        0xff, 0xbf, 0x81, 0x81, 0x81, 0x41, 0xd8, 0x54, 0xfb, 0xec, 0x7f, 0x06, 0x00, 0x22, 0x8f,
+        0x04, 0xbf, 0xb0, 0x75, 0xfe, 0x41, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+        0x42, 0x60, 0x82,
+    ];
+    const GRAY_INTERLACED: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x03, 0x08, 0x00, 0x00, 0x00, 0x01, 0x04,
+        0x44, 0xda, 0xf5, 0x00, 0x00, 0x00, 0x17, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0x60,
+        0x64, 0x60, 0x66, 0x10, 0x15, 0x67, 0x60, 0x62, 0x10, 0x63, 0xe0, 0xe6, 0xe1, 0x05, 0x00,
+        0x02, 0xa4, 0x00, 0x6d, 0x04, 0x66, 0xbf, 0xe9, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e,
+        0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    fn gray(v: u8) -> Color {
+        Color {
+            r: v,
+            g: v,
+            b: v,
+            a: 255,
+        }
+    }
+
+    #[test]
+    fn encoded_images_round_trip() {
+        for (width, height) in [(1, 1), (7, 5), (64, 3)] {
+            let image = gradient(width, height, true);
+            let encoded = encode(&image);
+            assert!(is_png(&encoded));
+            let decoded = decode(&encoded).unwrap();
+            assert_eq!((decoded.width, decoded.height), (width, height));
+            assert_eq!(decoded.pixels, image.pixels);
+        }
+    }
+
+    #[test]
+    fn packed_grayscale_is_scaled_to_eight_bits() {
+        let image = decode(GRAY_1BIT).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        let (w, b) = (gray(255), gray(0));
+        assert_eq!(image.pixels, [w, b, w, b, w, b]);
+    }
+
+    #[test]
+    fn palette_entries_take_their_transparency() {
+        let image = decode(PALETTE_2BIT).unwrap();
+        let red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        let green = Color {
+            r: 0,
+            g: 255,
+            b: 0,
+            a: 128,
+        };
+        let blue = Color {
+            r: 0,
+            g: 0,
+            b: 255,
+            a: 255,
+        };
+        assert_eq!(image.pixels, [red, green, blue, red]);
+    }
+
+    #[test]
+    fn sixteen_bit_rows_are_unfiltered_and_rounded() {
+        let image = decode(RGB_16BIT_SUB).unwrap();
+        let expected = [
+            Color {
+                r: 255,
+                g: 128,
+                b: 0,
+                a: 255,
+            },
+            Color {
+                r: 18,
+                g: 171,
+                b: 254,
+                a: 255,
+            },
+        ];
+        assert_eq!(image.pixels, expected);
+    }
+
+    #[test]
+    fn interlaced_passes_are_reassembled() {
+        let image = decode(GRAY_INTERLACED).unwrap();
+        let expected: Vec<Color> = (0..3)
+            .flat_map(|y| (0..3).map(move |x| gray(10 * y + x + 1)))
+            .collect();
+        assert_eq!(image.pixels, expected);
+    }
+
+    #[test]
+    fn damaged_files_are_errors() {
+        let encoded = encode(&gradient(4, 4, false));
+        for len in [8, 20, encoded.len() / 2, encoded.len() - 1] {
+            assert!(decode(&encoded[..len]).is_err(), "{len}");
+        }
+        // A pixel byte in the IDAT chunk, caught by the chunk CRC
+        let mut corrupted = encoded.clone();
+        corrupted[45] ^= 0x10;
+        assert!(decode(&corrupted).is_err());
+        assert!(!is_png(&encoded[1..]));
+    }
+}
@@ -0,0 +1,100 @@
+use super::{Image, ImageError, truncated};
+use crate::engine::shader::Color;
+
+pub fn encode(image: &Image, ascii: bool) -> Vec<u8> {
+    let magic = if ascii { "P3" } else { "P6" };
+    let mut out = format!("{magic}\n{} {}\n255\n", image.width, image.height).into_bytes();
+
+    if ascii {
+        // Plain PPM lines should stay under 70 characters
+        for row in image.pixels.chunks(image.width.max(1)) {
+            for (i, c) in row.iter().enumerate() {
+                let separator = if i % 5 == 4 || i + 1 == row.len() {
+                    '\n'
+                } else {
+                    ' '
+                };
+                out.extend(format!("{} {} {}{separator}", c.r, c.g, c.b).bytes());
+            }
+        }
+    } else {
+        for c in &image.pixels {
+            out.extend([c.r, c.g, c.b]);
+        }
+    }
+    out
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+    let ascii = bytes[1] == b'3';
+    let mut pos = 2;
+
+    let width = next_number(bytes, &mut pos)?;
+    let height = next_number(bytes, &mut pos)?;
+    let max = next_number(bytes, &mut pos)?;
+    if max == 0 || max > 65535 {
+        return Err(ImageError::Invalid(format!("maximum value {max}")));
+    }
+    let scale = |v: usize| ((v.min(max) * 255 + max / 2) / max) as u8;
+
+    // Every sample takes at least a byte, which also rules out absurd sizes
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .filter(|&n| n <= bytes.len())
+        .ok_or_else(truncated)?;
+    let mut samples = Vec::with_capacity(count);
+    if ascii {
+        for _ in 0..count {
+            samples.push(scale(next_number(bytes, &mut pos)?));
+        }
+    } else {
+        // A single whitespace character separates the header from the samples
+        pos += 1;
+        let size = if max < 256 { 1 } else { 2 };
+        let data = bytes.get(pos..pos + count * size).ok_or_else(truncated)?;
+        for sample in data.chunks(size) {
+            let value = match sample {
+                [v] => *v as usize,
+                _ => u16::from_be_bytes([sample[0], sample[1]]) as usize,
+            };
+            samples.push(scale(value));
+        }
+    }
+
+    let pixels = samples
+        .chunks(3)
+        .map(|s| Color {
+            r: s[0],
+            g: s[1],
+            b: s[2],
+            a: 255,
+        })
+        .collect();
+    Ok(Image::new(width, height, pixels))
+}
+
+// Parses the next decimal number, skipping whitespace and `#` comments
+fn next_number(bytes: &[u8], pos: &mut usize) -> Result<usize, ImageError> {
+    loop {
+        match bytes.get(*pos) {
+            Some(b'#') => {
+                while bytes.get(*pos).is_some_and(|&b| b != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            Some(_) => break,
+            None => return Err(truncated()),
+        }
+    }
+
+    let start = *pos;
+    while bytes.get(*pos).is_some_and(|b| b.is_ascii_digit()) {
+        *pos += 1;
+    }
+    std::str::from_utf8(&bytes[start..*pos])
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| ImageError::Invalid("expected a number in PPM data".into()))
+}
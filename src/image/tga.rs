@@ -0,0 +1,198 @@
+use super::{Image, ImageError, read_u16, truncated};
+use crate::engine::shader::Color;
+
+const HEADER_SIZE: usize = 18;
+
+// Image types
+const TRUE_COLOR: u8 = 2;
+const GRAYSCALE: u8 = 3;
+const RLE_TRUE_COLOR: u8 = 10;
+const RLE_GRAYSCALE: u8 = 11;
+
+// Descriptor bits
+const RIGHT_TO_LEFT: u8 = 0x10;
+const TOP_TO_BOTTOM: u8 = 0x20;
+
+/// Writes 32-bit BGRA pixels, stored from the top row down. TGA sizes are
+/// limited to 65535 pixels per side.
+pub fn encode(image: &Image, rle: bool) -> Result<Vec<u8>, ImageError> {
+    let (width, height) = (image.width, image.height);
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(ImageError::Unsupported(format!("{width}x{height} TGA")));
+    }
+
+    let mut out = vec![0; HEADER_SIZE];
+    out[2] = if rle { RLE_TRUE_COLOR } else { TRUE_COLOR };
+    out[12..14].copy_from_slice(&(width as u16).to_le_bytes());
+    out[14..16].copy_from_slice(&(height as u16).to_le_bytes());
+    out[16] = 32;
+    out[17] = TOP_TO_BOTTOM | 8;
+
+    let bgra = |c: &Color| [c.b, c.g, c.r, c.a];
+    if !rle {
+        for c in &image.pixels {
+            out.extend(bgra(c));
+        }
+        return Ok(out);
+    }
+
+    // Packets of up to 128 pixels, kept within a row
+    for row in image.pixels.chunks(image.width.max(1)) {
+        let mut i = 0;
+        while i < row.len() {
+            let run = row[i..]
+                .iter()
+                .take(128)
+                .take_while(|&c| *c == row[i])
+                .count();
+            if run > 1 {
+                out.push(0x80 | (run - 1) as u8);
+                out.extend(bgra(&row[i]));
+                i += run;
+                continue;
+            }
+
+            // Literal pixels up to the start of the next run
+            let mut end = i + 1;
+            while end < row.len() && end - i < 128 {
+                if end + 1 < row.len() && row[end] == row[end + 1] {
+                    break;
+                }
+                end += 1;
+            }
+            out.push((end - i - 1) as u8);
+            for c in &row[i..end] {
+                out.extend(bgra(c));
+            }
+            i = end;
+        }
+    }
+    Ok(out)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(truncated());
+    }
+    let id_length = bytes[0] as usize;
+    let color_map_type = bytes[1];
+    let image_type = bytes[2];
+    let width = read_u16(bytes, 12)? as usize;
+    let height = read_u16(bytes, 14)? as usize;
+    let depth = bytes[16];
+    let descriptor = bytes[17];
+
+    if color_map_type != 0 {
+        return Err(ImageError::Unsupported("color-mapped TGA".into()));
+    }
+    let rle = match image_type {
+        TRUE_COLOR | GRAYSCALE => false,
+        RLE_TRUE_COLOR | RLE_GRAYSCALE => true,
+        _ => {
+            return Err(ImageError::Unsupported(format!(
+                "TGA image type {image_type}"
+            )));
+        }
+    };
+    let gray = matches!(image_type, GRAYSCALE | RLE_GRAYSCALE);
+    let size = match (gray, depth) {
+        (true, 8) => 1,
+        (false, 16) => 2,
+        (false, 24) => 3,
+        (false, 32) => 4,
+        _ => return Err(ImageError::Unsupported(format!("{depth}-bit TGA"))),
+    };
+    let has_alpha = descriptor & 0x0F != 0;
+
+    let pixel = |p: &[u8]| match p {
+        [v] => Color {
+            r: *v,
+            g: *v,
+            b: *v,
+            a: 255,
+        },
+        // 5 bits per channel with an attribute bit: ARRRRRGG GGGBBBBB
+        [lo, hi] => {
+            let v = u16::from_le_bytes([*lo, *hi]);
+            let channel = |shift: u16| {
+                let c = ((v >> shift) & 0x1F) as u8;
+                (c << 3) | (c >> 2)
+            };
+            let a = if has_alpha && v & 0x8000 == 0 { 0 } else { 255 };
+            Color {
+                r: channel(10),
+                g: channel(5),
+                b: channel(0),
+                a,
+            }
+        }
+        [b, g, r] => Color {
+            r: *r,
+            g: *g,
+            b: *b,
+            a: 255,
+        },
+        [b, g, r, a] => Color {
+            r: *r,
+            g: *g,
+            b: *b,
+            a: if has_alpha { *a } else { 255 },
+        },
+        _ => unreachable!(),
+    };
+
+    let count = width * height;
+    let data = bytes.get(HEADER_SIZE + id_length..).ok_or_else(truncated)?;
+    let mut pixels = Vec::with_capacity(count.min(data.len()));
+    if rle {
+        let mut pos = 0;
+        while pixels.len() < count {
+            let header = *data.get(pos).ok_or_else(truncated)?;
+            let n = (header & 0x7F) as usize + 1;
+            pos += 1;
+            if header & 0x80 != 0 {
+                let p = data.get(pos..pos + size).ok_or_else(truncated)?;
+                pixels.extend(std::iter::repeat_n(pixel(p), n));
+                pos += size;
+            } else {
+                let run = data.get(pos..pos + n * size).ok_or_else(truncated)?;
+                pixels.extend(run.chunks(size).map(pixel));
+                pos += n * size;
+            }
+        }
+        pixels.truncate(count);
+    } else {
+        let data = data.get(..count * size).ok_or_else(truncated)?;
+        pixels.extend(data.chunks(size).map(pixel));
+    }
+
+    // Stored from the bottom row up unless the descriptor says otherwise
+    if descriptor & TOP_TO_BOTTOM == 0 {
+        let rows: Vec<&[Color]> = pixels.chunks(width.max(1)).rev().collect();
+        pixels = rows.concat();
+    }
+    if descriptor & RIGHT_TO_LEFT != 0 {
+        for row in pixels.chunks_mut(width.max(1)) {
+            row.reverse();
+        }
+    }
+
+    Ok(Image::new(width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_beyond_the_header_are_unsupported() {
+        let image = Image::new(65536, 0, Vec::new());
+        for rle in [false, true] {
+            assert!(matches!(
+                encode(&image, rle),
+                Err(ImageError::Unsupported(_))
+            ));
+        }
+        assert!(encode(&Image::new(65535, 0, Vec::new()), true).is_ok());
+    }
+}
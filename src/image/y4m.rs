@@ -0,0 +1,160 @@
+use std::io::Write;
+
+use super::{Image, ImageError};
+use crate::engine::shader::Color;
+
+/// Writes frames into a YUV4MPEG2 stream, the uncompressed video format read
+/// by ffmpeg and most encoders.
+///
+/// Frames are stored as limited-range BT.601 YCbCr with chroma subsampled to
+/// half width and height. Alpha is ignored.
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    width: usize,
+    height: usize,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Writes the stream header for `fps` frames per second.
+    pub fn new(mut writer: W, width: usize, height: usize, fps: u32) -> Result<Self, ImageError> {
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{width} H{height} F{fps}:1 Ip A1:1 C420jpeg"
+        )?;
+        Ok(Y4mWriter {
+            writer,
+            width,
+            height,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &Image) -> Result<(), ImageError> {
+        let (width, height) = (self.width, self.height);
+        if image.width != width || image.height != height {
+            return Err(ImageError::Invalid(format!(
+                "{}x{} frame in a {width}x{height} video",
+                image.width, image.height
+            )));
+        }
+
+        let rgb = |c: &Color| [c.r as f32, c.g as f32, c.b as f32];
+        let luma: Vec<u8> = image
+            .pixels
+            .iter()
+            .map(|c| {
+                let [r, g, b] = rgb(c);
+                (16.0 + 0.256788 * r + 0.504129 * g + 0.097906 * b).round() as u8
+            })
+            .collect();
+
+        // Chroma from the average color of each 2x2 block, which is where
+        // 4:2:0 JPEG siting places the sample
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        let mut cb = Vec::with_capacity(chroma_width * chroma_height);
+        let mut cr = Vec::with_capacity(chroma_width * chroma_height);
+        for y in 0..chroma_height {
+            for x in 0..chroma_width {
+                let mut sum = [0.0; 3];
+                let mut count = 0.0;
+                for py in y * 2..(y * 2 + 2).min(height) {
+                    for px in x * 2..(x * 2 + 2).min(width) {
+                        let c = rgb(&image.pixels[py * width + px]);
+                        for ch in 0..3 {
+                            sum[ch] += c[ch];
+                        }
+                        count += 1.0;
+                    }
+                }
+                let [r, g, b] = sum.map(|v| v / count);
+                cb.push((128.0 - 0.148223 * r - 0.290993 * g + 0.439216 * b).round() as u8);
+                cr.push((128.0 + 0.439216 * r - 0.367788 * g - 0.071427 * b).round() as u8);
+            }
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&luma)?;
+        self.writer.write_all(&cb)?;
+        self.writer.write_all(&cr)?;
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, ImageError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Color = Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+
+    fn solid(width: usize, height: usize, color: Color) -> Image {
+        Image::new(width, height, vec![color; width * height])
+    }
+
+    #[test]
+    fn header_describes_the_stream() {
+        let writer = Y4mWriter::new(Vec::new(), 320, 240, 30).unwrap();
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes, b"YUV4MPEG2 W320 H240 F30:1 Ip A1:1 C420jpeg\n");
+    }
+
+    #[test]
+    fn frames_hold_limited_range_planes() {
+        let red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        // Luma, then Cb and Cr
+        let cases = [
+            (Color::WHITE, [235, 128, 128]),
+            (BLACK, [16, 128, 128]),
+            (red, [81, 90, 240]),
+        ];
+        for (color, [y, cb, cr]) in cases {
+            let mut writer = Y4mWriter::new(Vec::new(), 3, 3, 25).unwrap();
+            let header_len = writer.writer.len();
+            writer.write_frame(&solid(3, 3, color)).unwrap();
+            let bytes = writer.finish().unwrap();
+
+            // Chroma is 2x2 for an odd 3x3 frame
+            let frame = &bytes[header_len..];
+            assert_eq!(&frame[..6], b"FRAME\n");
+            let planes = &frame[6..];
+            assert_eq!(planes.len(), 9 + 4 + 4);
+            assert!(planes[..9].iter().all(|&v| v == y), "{color:?}");
+            assert!(planes[9..13].iter().all(|&v| v == cb), "{color:?}");
+            assert!(planes[13..].iter().all(|&v| v == cr), "{color:?}");
+        }
+    }
+
+    #[test]
+    fn chroma_averages_each_block() {
+        let mut image = solid(2, 2, BLACK);
+        image.pixels[0] = Color::WHITE;
+        image.pixels[3] = Color::WHITE;
+        let mut writer = Y4mWriter::new(Vec::new(), 2, 2, 25).unwrap();
+        let header_len = writer.writer.len();
+        writer.write_frame(&image).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        assert_eq!(&bytes[header_len + 6..], [235, 16, 16, 235, 128, 128]);
+    }
+
+    #[test]
+    fn frames_must_match_the_video_size() {
+        let mut writer = Y4mWriter::new(Vec::new(), 4, 4, 25).unwrap();
+        assert!(writer.write_frame(&solid(4, 3, Color::WHITE)).is_err());
+        assert!(writer.write_frame(&solid(4, 4, Color::WHITE)).is_ok());
+    }
+}
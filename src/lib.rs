@@ -1,4 +1,5 @@
 pub mod engine;
+pub mod image;
 pub mod window;
 
 pub fn point(x: f32, y: f32, z: f32) -> engine::object::Point {
@@ -9,10 +10,22 @@ pub fn space(width: usize, height: usize, fps: usize) -> engine::space::Space {
     engine::space::Space::new(width, height, fps)
 }
 
+pub fn headless(width: usize, height: usize) -> engine::space::Space {
+    engine::space::Space::headless(width, height)
+}
+
 pub fn rotate_object(space: &mut engine::space::Space, id: usize, x: f32, y: f32, z: f32) {
     space.rotate_object(id, x, y, z);
 }
 
+pub fn translate_object(space: &mut engine::space::Space, id: usize, x: f32, y: f32, z: f32) {
+    space.translate_object(id, x, y, z);
+}
+
+pub fn scale_object(space: &mut engine::space::Space, id: usize, x: f32, y: f32, z: f32) {
+    space.scale_object(id, x, y, z);
+}
+
 pub fn rotate_all(space: &mut engine::space::Space, x: f32, y: f32, z: f32) {
     space.rotate_all(x, y, z);
 }
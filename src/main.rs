@@ -1,20 +1,25 @@
-use engine;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 fn main() {
     let mut space = engine::space(800, 600, 60);
+    // World +Y is up on screen, so the cube sits low in the frame
     let sphere_id = space.add_sphere(50.0, 0.0, 100.0, 30.0, 20.0);
-    let cube_id = space.add_cube(0.0, 50.0, 300.0, 40.0);
-    let another_sphere_id = space.add_sphere(-50.0, 20.0, 120.0, 10.0, 15.0);
-
-    let mut last_update = Instant::now();
+    let cube_id = space.add_cube(0.0, -50.0, 300.0, 40.0);
+    let another_sphere_id = space.add_sphere(-50.0, -20.0, 120.0, 10.0, 15.0);
+    space.set_screenshot_key(Some(engine::window::Key::F12));
+
+    // `cargo run -- turntable.gif` records the session at a steady 30 fps
+    if let Some(path) = std::env::args().nth(1) {
+        let recorder = engine::engine::recorder::Recorder::gif(path, 30)
+            .expect("30 fps is a valid frame rate");
+        if let Err(err) = space.start_recording(recorder) {
+            eprintln!("failed to finish previous recording: {err}");
+        }
+    }
 
-    while space.view.window.is_open() && !space.view.window.is_key_down(engine::window::Key::Escape)
-    {
-        // Calculate elapsed time for smooth animation
-        let now = Instant::now();
-        let elapsed = now.duration_since(last_update).as_secs_f32();
-        last_update = now;
+    while space.view.is_open() && !space.view.is_key_down(engine::window::Key::Escape) {
+        // Elapsed time for smooth animation, fixed while recording
+        let elapsed = space.delta_time();
 
         // Rotate the sphere - adjust rotation speeds as desired
         engine::rotate_object(
@@ -42,8 +47,15 @@ fn main() {
         );
 
         space.update();
+        if let Some(err) = space.take_error() {
+            eprintln!("failed to save a frame: {err}");
+        }
 
         // Add a small delay to limit CPU usage
         std::thread::sleep(Duration::from_millis(10));
     }
+
+    if let Err(err) = space.stop_recording() {
+        eprintln!("failed to finish recording: {err}");
+    }
 }
@@ -1,5 +1,34 @@
 pub use minifb::{Key, Window, WindowOptions};
 
+use crate::engine::pixel::PixelFormat;
+
+/// A surface that rendered frames are presented to.
+///
+/// `Space` only talks to its display through this trait, so the minifb window
+/// is just one backend and the engine can also run without a screen.
+pub trait Display {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn buffer(&self) -> &[u32];
+    fn buffer_mut(&mut self) -> &mut [u32];
+
+    /// How colors are packed into the buffer.
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::Argb8888
+    }
+
+    /// Shows the current contents of the buffer.
+    fn present(&mut self);
+
+    fn is_open(&self) -> bool {
+        true
+    }
+
+    fn is_key_down(&self, _key: Key) -> bool {
+        false
+    }
+}
+
 pub struct View {
     pub width: usize,
     pub height: usize,
@@ -32,3 +61,92 @@ impl View {
             .unwrap();
     }
 }
+
+impl Display for View {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn buffer(&self) -> &[u32] {
+        &self.buffer
+    }
+
+    fn buffer_mut(&mut self) -> &mut [u32] {
+        &mut self.buffer
+    }
+
+    // minifb reads pixels as 0x00RRGGBB
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::Argb8888
+    }
+
+    fn present(&mut self) {
+        self.update();
+    }
+
+    fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.window.is_key_down(key)
+    }
+}
+
+/// Off-screen display that only owns a framebuffer.
+///
+/// Frames rendered into it can be read back through `buffer()`, which makes it
+/// usable on machines without a display server.
+pub struct Headless {
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+    pub buffer: Vec<u32>,
+    pub frames: usize,
+}
+
+impl Headless {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_format(width, height, PixelFormat::Argb8888)
+    }
+
+    pub fn with_format(width: usize, height: usize, format: PixelFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            buffer: vec![0; width * height],
+            frames: 0,
+        }
+    }
+}
+
+impl Display for Headless {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn buffer(&self) -> &[u32] {
+        &self.buffer
+    }
+
+    fn buffer_mut(&mut self) -> &mut [u32] {
+        &mut self.buffer
+    }
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.format
+    }
+
+    fn present(&mut self) {
+        self.frames += 1;
+    }
+}
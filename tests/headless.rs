@@ -0,0 +1,631 @@
+use engine::engine::light::Light;
+use engine::engine::object::{Edge, Object, Point, Triangle};
+use engine::engine::pixel::PixelFormat;
+use engine::engine::recorder::Recorder;
+use engine::engine::render::{AntiAliasing, CullMode, RenderMode, Transparency};
+use engine::engine::shader::{Color, ShadingMode};
+use engine::engine::space::{Projection, Space};
+use engine::image::ImageFormat;
+use engine::window::Headless;
+
+const SIZE: usize = 64;
+
+const RED: Color = Color {
+    r: 255,
+    g: 0,
+    b: 0,
+    a: 255,
+};
+
+const BLACK: Color = Color {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 255,
+};
+
+// A red triangle filling the middle of the view of the default camera. Its
+// normal points away from the camera, so it is a back face.
+fn triangle(id: usize) -> Object {
+    let mut object = Object::new_cube(id, 1.0, Point::default());
+    object.points = vec![
+        Point::new(-20.0, -20.0, 0.0),
+        Point::new(20.0, -20.0, 0.0),
+        Point::new(0.0, 20.0, 0.0),
+    ];
+    object.original_points = object.points.clone();
+    object.edges = vec![
+        Edge { start: 0, end: 1 },
+        Edge { start: 1, end: 2 },
+        Edge { start: 2, end: 0 },
+    ];
+    object.triangles = vec![Triangle { a: 0, b: 1, c: 2 }];
+    object.uvs = None;
+    object.color = RED;
+    object.render_mode = RenderMode::Solid;
+    object
+}
+
+fn pixel(space: &Space, x: usize, y: usize) -> Color {
+    let format = space.view.pixel_format();
+    format.unpack(space.view.buffer()[y * space.view.width() + x])
+}
+
+#[test]
+fn empty_scene_clears_to_black() {
+    let mut space = Space::headless(SIZE, SIZE);
+    space.view.buffer_mut().fill(0x00FF_FFFF);
+    space.update();
+
+    assert_eq!(space.view.buffer().len(), SIZE * SIZE);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            assert_eq!(pixel(&space, x, y), BLACK, "pixel {x},{y}");
+        }
+    }
+}
+
+#[test]
+fn filled_triangle_covers_its_interior_only() {
+    let mut space = Space::headless(SIZE, SIZE);
+    let mut object = triangle(0);
+    object.cull_mode = CullMode::None;
+    space.add_object(object);
+    space.update();
+
+    let center = SIZE / 2;
+    assert_eq!(pixel(&space, center, center), RED);
+    assert_eq!(pixel(&space, center, center + 10), RED);
+    // Beside the apex and below the base
+    assert_eq!(pixel(&space, center - 15, center - 15), BLACK);
+    assert_eq!(pixel(&space, center, SIZE - 2), BLACK);
+    assert_eq!(pixel(&space, 0, 0), BLACK);
+
+    let red = space
+        .view
+        .buffer()
+        .iter()
+        .filter(|&&p| space.view.pixel_format().unpack(p) == RED)
+        .count();
+    // Half the base times the height. At a distance of 50 with a 60 degree
+    // field of view the view is 57.7 units high, so 40 units are 44 pixels.
+    let expected = 0.5 * (40.0 * SIZE as f32 / 57.735).powi(2);
+    assert!(
+        (red as f32 - expected).abs() < expected * 0.1,
+        "{red} red pixels, expected about {expected}"
+    );
+}
+
+#[test]
+fn every_pixel_format_keeps_red_red() {
+    let blue = Color {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+    for format in [
+        PixelFormat::Argb8888,
+        PixelFormat::Abgr8888,
+        PixelFormat::Rgb565,
+        PixelFormat::Gray8,
+    ] {
+        let mut space = Space::with_display(Box::new(Headless::with_format(SIZE, SIZE, format)));
+        let mut object = triangle(0);
+        object.cull_mode = CullMode::None;
+        space.add_object(object);
+        space.update();
+
+        let center = pixel(&space, SIZE / 2, SIZE / 2);
+        assert_eq!(center, format.unpack(format.pack(RED)), "{format:?}");
+        assert_ne!(center, format.unpack(format.pack(blue)), "{format:?}");
+        if format != PixelFormat::Gray8 {
+            assert_eq!(center, RED, "{format:?}");
+        }
+        assert_eq!(pixel(&space, 0, 0), BLACK, "{format:?}");
+    }
+}
+
+#[test]
+fn back_faces_are_culled() {
+    let cases = [
+        (CullMode::Back, BLACK),
+        (CullMode::Front, RED),
+        (CullMode::None, RED),
+    ];
+    for (cull_mode, expected) in cases {
+        let mut space = Space::headless(SIZE, SIZE);
+        let mut object = triangle(0);
+        object.cull_mode = cull_mode;
+        space.add_object(object);
+        space.update();
+
+        assert_eq!(pixel(&space, SIZE / 2, SIZE / 2), expected, "{cull_mode:?}");
+    }
+}
+
+// Pixels the triangle partly covers, whose red is between black and full
+fn partly_red_pixels(space: &Space) -> usize {
+    let format = space.view.pixel_format();
+    space
+        .view
+        .buffer()
+        .iter()
+        .map(|&p| format.unpack(p))
+        .filter(|c| c.r > 0 && c.r < 255 && c.g == 0 && c.b == 0)
+        .count()
+}
+
+#[test]
+fn antialiasing_blends_triangle_edges_only() {
+    for antialiasing in [AntiAliasing::Msaa4x, AntiAliasing::Supersample2x] {
+        let mut space = Space::headless(SIZE, SIZE);
+        space.set_antialiasing(antialiasing);
+        let mut object = triangle(0);
+        object.cull_mode = CullMode::None;
+        space.add_object(object);
+        space.update();
+
+        assert_eq!(space.view.buffer().len(), SIZE * SIZE, "{antialiasing:?}");
+        let center = SIZE / 2;
+        assert_eq!(pixel(&space, center, center), RED, "{antialiasing:?}");
+        assert_eq!(pixel(&space, center, center + 10), RED, "{antialiasing:?}");
+        assert_eq!(pixel(&space, 0, 0), BLACK, "{antialiasing:?}");
+
+        // Both slanted sides get blended pixels along their length
+        let blended = |xs: std::ops::Range<usize>| {
+            (center - 10..center + 10)
+                .flat_map(|y| xs.clone().map(move |x| (x, y)))
+                .filter(|&(x, y)| {
+                    let c = pixel(&space, x, y);
+                    c.r > 0 && c.r < 255
+                })
+                .count()
+        };
+        assert!(blended(0..center) >= 10, "{antialiasing:?}: left side");
+        assert!(blended(center..SIZE) >= 10, "{antialiasing:?}: right side");
+        assert!(partly_red_pixels(&space) >= 40, "{antialiasing:?}");
+    }
+
+    let mut aliased = Space::headless(SIZE, SIZE);
+    let mut object = triangle(0);
+    object.cull_mode = CullMode::None;
+    aliased.add_object(object);
+    aliased.update();
+    assert_eq!(partly_red_pixels(&aliased), 0);
+}
+
+#[test]
+fn switching_antialiasing_keeps_the_display_size() {
+    let mut space = Space::headless(SIZE, SIZE);
+    let mut object = triangle(0);
+    object.cull_mode = CullMode::None;
+    space.add_object(object);
+
+    for antialiasing in [
+        AntiAliasing::Supersample4x,
+        AntiAliasing::Msaa2x,
+        AntiAliasing::Supersample2x,
+        AntiAliasing::None,
+    ] {
+        space.set_antialiasing(antialiasing);
+        assert_eq!(space.antialiasing(), antialiasing);
+        space.update();
+        assert_eq!(space.view.buffer().len(), SIZE * SIZE, "{antialiasing:?}");
+        assert_eq!(pixel(&space, SIZE / 2, SIZE / 2), RED, "{antialiasing:?}");
+    }
+    // Back without antialiasing, no sample of the previous targets is left
+    assert_eq!(partly_red_pixels(&space), 0);
+}
+
+#[test]
+fn front_faces_are_drawn_with_back_face_culling() {
+    let mut space = Space::headless(SIZE, SIZE);
+    let mut object = triangle(0);
+    object.triangles = vec![Triangle { a: 0, b: 2, c: 1 }];
+    space.add_object(object);
+    space.update();
+
+    assert_eq!(pixel(&space, SIZE / 2, SIZE / 2), RED);
+}
+
+#[test]
+fn flat_shading_lights_a_face_uniformly() {
+    let mut space = Space::headless(SIZE, SIZE);
+    let mut object = triangle(0);
+    object.triangles = vec![Triangle { a: 0, b: 2, c: 1 }];
+    object.color = Color::WHITE;
+    object.shading = ShadingMode::Flat;
+    space.add_object(object);
+    // Close to one corner, so lighting per pixel would vary a lot
+    space.add_light(Light::point(
+        Point::new(-20.0, -20.0, -5.0),
+        Color::WHITE,
+        1.0,
+    ));
+    space.update();
+
+    let center = SIZE / 2;
+    let lit = pixel(&space, center, center);
+    assert_ne!(lit, BLACK);
+    for (x, y) in [
+        (center - 15, center + 10),
+        (center + 15, center + 10),
+        (center, center - 10),
+    ] {
+        assert_eq!(pixel(&space, x, y), lit, "pixel {x},{y}");
+    }
+}
+
+// Every pixel of a translucent white cube over black, drawn unlit, is
+// covered by exactly one front face
+fn assert_translucent_cube_blends_once(transparency: Transparency, rotation: (f32, f32, f32)) {
+    let mut space = Space::headless(SIZE, SIZE);
+    space.camera_mut().projection = Projection::Orthographic {
+        height: SIZE as f32,
+    };
+    space.set_transparency(transparency);
+    let mut cube = Object::new_cube(0, 30.0, Point::default());
+    cube.color = Color {
+        a: 128,
+        ..Color::WHITE
+    };
+    cube.render_mode = RenderMode::Solid;
+    cube.set_rotation(rotation.0, rotation.1, rotation.2);
+    space.add_object(cube);
+    space.update();
+
+    let gray = Color {
+        r: 128,
+        g: 128,
+        b: 128,
+        a: 255,
+    };
+    let mut covered = 0;
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let color = pixel(&space, x, y);
+            if color != BLACK {
+                assert_eq!(color, gray, "pixel {x},{y} with {transparency:?}");
+                covered += 1;
+            }
+        }
+    }
+    assert!(covered >= 900, "{covered} pixels covered");
+}
+
+#[test]
+fn shared_edges_of_translucent_faces_blend_once() {
+    for transparency in [Transparency::Sorted, Transparency::WeightedBlended] {
+        assert_translucent_cube_blends_once(transparency, (0.0, 0.0, 0.0));
+        assert_translucent_cube_blends_once(transparency, (0.5, 0.7, 0.2));
+    }
+}
+
+#[test]
+fn weighted_blending_favors_nearer_faces_in_orthographic_views() {
+    let mut space = Space::headless(SIZE, SIZE);
+    space.camera_mut().projection = Projection::Orthographic {
+        height: SIZE as f32,
+    };
+    space.set_transparency(Transparency::WeightedBlended);
+    let cubes = [
+        (Color { a: 128, ..RED }, 0.0),
+        (
+            Color {
+                r: 0,
+                g: 0,
+                b: 255,
+                a: 128,
+            },
+            150.0,
+        ),
+    ];
+    for (id, (color, z)) in cubes.into_iter().enumerate() {
+        let mut cube = Object::new_cube(id, 30.0, Point::new(0.0, 0.0, z));
+        cube.color = color;
+        cube.render_mode = RenderMode::Solid;
+        space.add_object(cube);
+    }
+    space.update();
+
+    let color = pixel(&space, SIZE / 2, SIZE / 2);
+    assert!(color.r as u32 > color.b as u32 * 4, "{color:?}");
+}
+
+#[test]
+fn vertex_points_of_translucent_objects_are_blended() {
+    let mut space = Space::headless(SIZE, SIZE);
+
+    // An opaque blue wall behind the points
+    let mut wall = Object::new_cube(0, 100.0, Point::new(0.0, 0.0, 60.0));
+    wall.color = Color {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+    wall.render_mode = RenderMode::Solid;
+    space.add_object(wall);
+
+    let mut object = triangle(1);
+    object.color = Color { a: 128, ..RED };
+    object.render_mode = RenderMode::Wireframe;
+    // Only the points are drawn
+    object.edges.clear();
+    let corners = object.points.clone();
+    space.add_object(object);
+    space.update();
+
+    for corner in corners {
+        let (x, y) = screen_position(&space, corner);
+        let color = pixel(&space, x as usize, y as usize);
+        assert!((120..=136).contains(&color.r), "{corner:?}: {color:?}");
+        assert!((119..=135).contains(&color.b), "{corner:?}: {color:?}");
+        assert_eq!(color.g, 0);
+    }
+}
+
+#[test]
+fn recorders_need_a_frame_rate() {
+    assert!(Recorder::gif("unused.gif", 0).is_err());
+    assert!(Recorder::y4m("unused.y4m", 0).is_err());
+    assert!(Recorder::images("unused", ImageFormat::Png, 0).is_err());
+}
+
+#[test]
+fn failed_captures_stop_the_recording_and_are_kept() {
+    let mut space = Space::headless(SIZE, SIZE);
+    let path = std::env::temp_dir().join("engine-missing-directory/video.y4m");
+    space
+        .start_recording(Recorder::y4m(path, 30).unwrap())
+        .unwrap();
+    space.update();
+
+    assert!(!space.is_recording());
+    assert!(space.take_error().is_some());
+    assert!(space.take_error().is_none());
+}
+
+#[test]
+fn nearer_objects_hide_farther_ones_in_any_order() {
+    let blue = Color {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+    // Both ids and insertion orders, so neither decides what is in front
+    for (cube_id, sphere_id) in [(0, 1), (1, 0)] {
+        for cube_first in [true, false] {
+            let mut cube = Object::new_cube(cube_id, 20.0, Point::new(0.0, 0.0, 50.0));
+            cube.color = RED;
+            cube.render_mode = RenderMode::Solid;
+            let mut sphere =
+                Object::new_sphere(sphere_id, 100.0, Point::new(0.0, 0.0, 200.0), 12.0);
+            sphere.color = blue;
+            sphere.render_mode = RenderMode::Solid;
+
+            let mut space = Space::headless(SIZE, SIZE);
+            if cube_first {
+                space.add_object(cube);
+                space.add_object(sphere);
+            } else {
+                space.add_object(sphere);
+                space.add_object(cube);
+            }
+            space.update();
+
+            let center = SIZE / 2;
+            assert_eq!(pixel(&space, center, center), RED, "cube {cube_id}");
+            // The sphere is larger on screen, so it shows around the cube
+            assert_eq!(pixel(&space, center, center - 18), blue, "cube {cube_id}");
+        }
+    }
+}
+
+#[test]
+fn coplanar_objects_are_drawn_in_id_order() {
+    let blue = Color {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+    for red_first in [true, false] {
+        let mut red = triangle(0);
+        red.cull_mode = CullMode::None;
+        let mut other = triangle(1);
+        other.cull_mode = CullMode::None;
+        other.color = blue;
+
+        let mut space = Space::headless(SIZE, SIZE);
+        if red_first {
+            space.add_object(red);
+            space.add_object(other);
+        } else {
+            space.add_object(other);
+            space.add_object(red);
+        }
+        space.update();
+
+        // Equal depths fail the depth test, so the lower id keeps the pixel
+        assert_eq!(pixel(&space, SIZE / 2, SIZE / 2), RED);
+    }
+}
+
+#[test]
+fn vertex_colors_are_interpolated_across_faces() {
+    let mut space = Space::headless(SIZE, SIZE);
+    let mut object = triangle(0);
+    object.cull_mode = CullMode::None;
+    object.vertex_colors = Some(vec![
+        RED,
+        Color {
+            r: 0,
+            g: 255,
+            b: 0,
+            a: 255,
+        },
+        Color {
+            r: 0,
+            g: 0,
+            b: 255,
+            a: 255,
+        },
+    ]);
+    space.add_object(object);
+    space.update();
+
+    // The centroid, a third of the way up from the base, mixes all three
+    let center = SIZE / 2;
+    let mixed = pixel(&space, center, center + 7);
+    for channel in [mixed.r, mixed.g, mixed.b] {
+        assert!(channel.abs_diff(85) <= 12, "{mixed:?}");
+    }
+
+    // Near each corner its own color dominates: bottom left, bottom right, top
+    let corner = |x, y| pixel(&space, x, y);
+    let near_red = corner(center - 17, center + 20);
+    let near_green = corner(center + 17, center + 20);
+    let near_blue = corner(center, center - 18);
+    assert!(
+        near_red.r > 200 && near_red.g < 50 && near_red.b < 50,
+        "{near_red:?}"
+    );
+    assert!(
+        near_green.g > 200 && near_green.r < 50 && near_green.b < 50,
+        "{near_green:?}"
+    );
+    assert!(
+        near_blue.b > 200 && near_blue.r < 50 && near_blue.g < 50,
+        "{near_blue:?}"
+    );
+}
+
+// Smallest and largest column and row of the pixels covered by a small cube
+// at `position`
+fn cube_bounds(position: Point) -> (usize, usize, usize, usize) {
+    let mut space = Space::headless(SIZE, SIZE);
+    let mut cube = Object::new_cube(0, 6.0, position);
+    cube.render_mode = RenderMode::Solid;
+    space.add_object(cube);
+    space.update();
+
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            if pixel(&space, x, y) != BLACK {
+                (min_x, min_y) = (min_x.min(x), min_y.min(y));
+                (max_x, max_y) = (max_x.max(x), max_y.max(y));
+            }
+        }
+    }
+    assert!(min_x <= max_x, "nothing drawn at {position:?}");
+    (min_x, min_y, max_x, max_y)
+}
+
+#[test]
+fn world_up_is_the_top_of_the_screen() {
+    let half = SIZE / 2;
+
+    let (min_x, _, max_x, max_y) = cube_bounds(Point::new(0.0, 15.0, 0.0));
+    assert!(max_y < half, "+Y reaches row {max_y}");
+    assert!(min_x < half && max_x >= half);
+
+    let (min_x, min_y, _, max_y) = cube_bounds(Point::new(15.0, 0.0, 0.0));
+    assert!(min_x >= half, "+X reaches column {min_x}");
+    assert!(min_y < half && max_y >= half);
+}
+
+// Where a world position lands in the buffer
+fn screen_position(space: &Space, point: Point) -> (f32, f32) {
+    let ndc = (space.camera().view_projection() * point.extend(1.0)).project();
+    (
+        (ndc.x * 0.5 + 0.5) * SIZE as f32,
+        (0.5 - ndc.y * 0.5) * SIZE as f32,
+    )
+}
+
+// Distance from `p` to the segment from `a` to `b`
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let t = (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+// A turned hidden-line cube shows every edge but the three meeting at its
+// farthest corner, which are behind its faces
+fn assert_back_edges_hidden(space: &mut Space, center: Point, size: f32) {
+    let mut cube = Object::new_cube(0, size, center);
+    cube.render_mode = RenderMode::HiddenLine;
+    cube.set_rotation(0.5, 0.6, 0.0);
+    let eye = space.camera().pos;
+    let far = (0..8)
+        .max_by(|&a, &b| {
+            let distance = |i: usize| (cube.points[i] - eye).length();
+            distance(a).total_cmp(&distance(b))
+        })
+        .unwrap();
+    let screen: Vec<(f32, f32)> = cube
+        .points
+        .iter()
+        .map(|&p| screen_position(space, p))
+        .collect();
+    let edges: Vec<(usize, usize)> = cube.edges.iter().map(|e| (e.start, e.end)).collect();
+    let touches_far = |&(start, end): &(usize, usize)| start == far || end == far;
+    let hidden: Vec<_> = edges.iter().copied().filter(touches_far).collect();
+    let visible: Vec<_> = edges.iter().copied().filter(|e| !touches_far(e)).collect();
+    space.add_object(cube);
+    space.update();
+
+    let at = |(a, b): (usize, usize), t: f32| {
+        let (a, b) = (screen[a], screen[b]);
+        (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+    };
+    let lit = |(x, y): (f32, f32)| pixel(space, x as usize, y as usize) != BLACK;
+    let near_lit = |(x, y): (f32, f32)| {
+        [-1.0, 0.0, 1.0]
+            .iter()
+            .any(|dy| [-1.0, 0.0, 1.0].iter().any(|dx| lit((x + dx, y + dy))))
+    };
+
+    for &edge in &visible {
+        for t in [0.3, 0.5, 0.7] {
+            assert!(near_lit(at(edge, t)), "visible edge {edge:?} at {t}");
+        }
+    }
+
+    // Along the hidden edges from the far corner, away from where they cross
+    // visible ones on screen
+    let mut checked = 0;
+    for &(start, end) in &hidden {
+        let edge = if start == far {
+            (start, end)
+        } else {
+            (end, start)
+        };
+        for t in [0.2, 0.3, 0.4, 0.5, 0.6] {
+            let p = at(edge, t);
+            let crossing = visible
+                .iter()
+                .any(|&(a, b)| distance_to_segment(p, screen[a], screen[b]) < 2.0);
+            if !crossing {
+                assert!(!lit(p), "hidden edge {edge:?} at {t}");
+                checked += 1;
+            }
+        }
+    }
+    assert!(checked >= 8, "only {checked} hidden samples");
+}
+
+#[test]
+fn hidden_line_cubes_hide_their_back_edges() {
+    let mut space = Space::headless(SIZE, SIZE);
+    assert_back_edges_hidden(&mut space, Point::default(), 30.0);
+
+    // Far away through a narrow field of view, where depth is least precise
+    let mut space = Space::headless(SIZE, SIZE);
+    space.camera_mut().projection = Projection::Perspective { fov: 1.0 };
+    assert_back_edges_hidden(&mut space, Point::new(0.0, 0.0, 5000.0), 30.0);
+}